@@ -0,0 +1,668 @@
+use std::f64::consts::PI;
+
+use cairo::{Context, FontSlant, FontWeight, ImageSurface, LineCap, LineJoin, Operator};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Color;
+use crate::redact::{Backdrop, Redaction};
+
+/// 画笔相邻两点的最小距离，逻辑像素
+const MIN_POINT_DISTANCE: f64 = 1.5;
+/// 荧光笔的宽度是线宽的倍数
+const HIGHLIGHT_SCALE: f64 = 4.0;
+const HIGHLIGHT_ALPHA: f64 = 0.6;
+/// 点击标注时允许的偏差，逻辑像素
+const HIT_TOLERANCE: f64 = 4.0;
+const MIN_FONT_SIZE: f64 = 6.0;
+
+/// 标注工具
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    /// 选择、移动和缩放已有的标注
+    Select,
+    Rectangle,
+    Ellipse,
+    Line,
+    Arrow,
+    /// 自由画笔
+    Pen,
+    /// 半透明荧光笔，以正片叠底混合
+    Highlighter,
+    Text,
+    /// 自动编号的步骤标记
+    Step,
+    /// 带尾巴的对话气泡
+    Bubble,
+    Redact,
+}
+
+impl Tool {
+    /// 工具栏中的顺序
+    pub const ALL: [Tool; 11] = [
+        Tool::Select,
+        Tool::Rectangle,
+        Tool::Ellipse,
+        Tool::Line,
+        Tool::Arrow,
+        Tool::Pen,
+        Tool::Highlighter,
+        Tool::Text,
+        Tool::Step,
+        Tool::Bubble,
+        Tool::Redact,
+    ];
+
+    /// 需要输入文字的工具
+    pub fn is_text(&self) -> bool {
+        matches!(self, Tool::Text | Tool::Bubble)
+    }
+}
+
+/// 新标注使用的样式
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    pub color: Color,
+    /// 线宽，逻辑像素
+    pub width: f64,
+    /// 字体族，如 Sans、Monospace
+    pub font: String,
+    pub font_size: f64,
+    pub redaction: Redaction,
+}
+
+/// 标注的几何形状，坐标均为全局逻辑坐标
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Shape {
+    Rectangle {
+        start: (f64, f64),
+        end: (f64, f64),
+    },
+    Ellipse {
+        start: (f64, f64),
+        end: (f64, f64),
+    },
+    Line {
+        start: (f64, f64),
+        end: (f64, f64),
+    },
+    Arrow {
+        start: (f64, f64),
+        end: (f64, f64),
+    },
+    /// 画笔经过的点
+    Pen {
+        points: Vec<(f64, f64)>,
+    },
+    /// 荧光笔经过的点
+    Highlight {
+        points: Vec<(f64, f64)>,
+    },
+    /// 多行文本，pos 为左上角
+    Text {
+        pos: (f64, f64),
+        text: String,
+        font: String,
+        size: f64,
+    },
+    /// 圆形步骤标记，number 从 1 开始
+    Step {
+        center: (f64, f64),
+        number: u32,
+    },
+    /// 对话气泡，以 pos 为中心，尾巴指向 tip
+    Bubble {
+        tip: (f64, f64),
+        pos: (f64, f64),
+        text: String,
+        font: String,
+        size: f64,
+    },
+    /// 打码区域
+    Redact {
+        start: (f64, f64),
+        end: (f64, f64),
+        redaction: Redaction,
+    },
+}
+
+/// 绘制在截图上的一个标注
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub shape: Shape,
+    pub color: Color,
+    /// 线宽，逻辑像素
+    pub width: f64,
+}
+
+impl Annotation {
+    /// 用指定工具在 pos 处开始一个标注，选择工具不创建标注
+    pub fn new(tool: Tool, pos: (f64, f64), style: &Style) -> Self {
+        let shape = match tool {
+            Tool::Select => unreachable!("select tool does not create annotations"),
+            Tool::Rectangle => Shape::Rectangle {
+                start: pos,
+                end: pos,
+            },
+            Tool::Ellipse => Shape::Ellipse {
+                start: pos,
+                end: pos,
+            },
+            Tool::Line => Shape::Line {
+                start: pos,
+                end: pos,
+            },
+            Tool::Arrow => Shape::Arrow {
+                start: pos,
+                end: pos,
+            },
+            Tool::Pen => Shape::Pen { points: vec![pos] },
+            Tool::Highlighter => Shape::Highlight { points: vec![pos] },
+            Tool::Text => Shape::Text {
+                pos,
+                text: String::new(),
+                font: style.font.clone(),
+                size: style.font_size,
+            },
+            // NOTE: 编号由 EditMode 在加入标注时重新计算
+            Tool::Step => Shape::Step {
+                center: pos,
+                number: 1,
+            },
+            Tool::Bubble => Shape::Bubble {
+                tip: pos,
+                pos,
+                text: String::new(),
+                font: style.font.clone(),
+                size: style.font_size,
+            },
+            Tool::Redact => Shape::Redact {
+                start: pos,
+                end: pos,
+                redaction: style.redaction,
+            },
+        };
+        Self {
+            shape,
+            color: style.color,
+            width: style.width,
+        }
+    }
+
+    /// 拖动时更新终点，步骤标记跟随移动，气泡从尾巴处拖出
+    pub fn drag_to(&mut self, pos: (f64, f64)) {
+        match &mut self.shape {
+            Shape::Rectangle { end, .. }
+            | Shape::Ellipse { end, .. }
+            | Shape::Line { end, .. }
+            | Shape::Arrow { end, .. }
+            | Shape::Redact { end, .. } => *end = pos,
+            Shape::Step { center, .. } => *center = pos,
+            Shape::Pen { points } | Shape::Highlight { points } => {
+                // NOTE: 忽略过近的点，减少抖动
+                if let Some(last) = points.last()
+                    && (pos.0 - last.0).hypot(pos.1 - last.1) < MIN_POINT_DISTANCE
+                {
+                    return;
+                }
+                points.push(pos);
+            }
+            Shape::Bubble { pos: bubble, .. } => *bubble = pos,
+            Shape::Text { .. } => {}
+        }
+    }
+
+    /// 文本和气泡中可编辑的文字
+    pub fn text_mut(&mut self) -> Option<&mut String> {
+        match &mut self.shape {
+            Shape::Text { text, .. } | Shape::Bubble { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// 步骤标记的半径，随线宽变化
+    fn step_radius(&self) -> f64 {
+        10.0 + self.width * 1.5
+    }
+
+    /// 标注的外接矩形，不含线宽
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        match &self.shape {
+            Shape::Text {
+                pos,
+                text,
+                font,
+                size,
+            } => {
+                let ctx = scratch_context();
+                let (width, height) = text_size(&ctx, text, font, *size);
+                (pos.0, pos.1, width, height)
+            }
+            Shape::Bubble {
+                tip,
+                pos,
+                text,
+                font,
+                size,
+            } => {
+                let (_, rect) = bubble_layout(&scratch_context(), *pos, text, font, *size);
+                union(rect, *tip)
+            }
+            &Shape::Step { center, .. } => {
+                let radius = self.step_radius();
+                (
+                    center.0 - radius,
+                    center.1 - radius,
+                    radius * 2.0,
+                    radius * 2.0,
+                )
+            }
+            _ => {
+                let mut points = self.points();
+                let first = *points.next().unwrap();
+                points.fold((first.0, first.1, 0.0, 0.0), |rect, p| union(rect, *p))
+            }
+        }
+    }
+
+    /// pos 是否落在标注上，线条按到线段的距离判断
+    pub fn contains(&self, pos: (f64, f64)) -> bool {
+        let tolerance = self.width / 2.0 + HIT_TOLERANCE;
+        let near = |points: &[(f64, f64)], tolerance: f64| {
+            points
+                .windows(2)
+                .any(|pair| segment_distance(pos, pair[0], pair[1]) <= tolerance)
+        };
+        match &self.shape {
+            &Shape::Line { start, end } | &Shape::Arrow { start, end } => {
+                near(&[start, end], tolerance)
+            }
+            Shape::Pen { points } => near(points, tolerance),
+            Shape::Highlight { points } => near(points, tolerance * HIGHLIGHT_SCALE),
+            &Shape::Step { center, .. } => {
+                (pos.0 - center.0).hypot(pos.1 - center.1) <= self.step_radius()
+            }
+            _ => {
+                let (x, y, w, h) = self.bounds();
+                pos.0 >= x - tolerance
+                    && pos.0 <= x + w + tolerance
+                    && pos.1 >= y - tolerance
+                    && pos.1 <= y + h + tolerance
+            }
+        }
+    }
+
+    /// 标注的所有控制点
+    fn points(&self) -> impl Iterator<Item = &(f64, f64)> {
+        let points: Vec<&(f64, f64)> = match &self.shape {
+            Shape::Rectangle { start, end }
+            | Shape::Ellipse { start, end }
+            | Shape::Line { start, end }
+            | Shape::Arrow { start, end }
+            | Shape::Redact { start, end, .. } => vec![start, end],
+            Shape::Pen { points } | Shape::Highlight { points } => points.iter().collect(),
+            Shape::Text { pos, .. } => vec![pos],
+            Shape::Step { center, .. } => vec![center],
+            Shape::Bubble { tip, pos, .. } => vec![tip, pos],
+        };
+        points.into_iter()
+    }
+
+    fn points_mut(&mut self) -> Vec<&mut (f64, f64)> {
+        match &mut self.shape {
+            Shape::Rectangle { start, end }
+            | Shape::Ellipse { start, end }
+            | Shape::Line { start, end }
+            | Shape::Arrow { start, end }
+            | Shape::Redact { start, end, .. } => vec![start, end],
+            Shape::Pen { points } | Shape::Highlight { points } => points.iter_mut().collect(),
+            Shape::Text { pos, .. } => vec![pos],
+            Shape::Step { center, .. } => vec![center],
+            Shape::Bubble { tip, pos, .. } => vec![tip, pos],
+        }
+    }
+
+    /// 平移标注
+    pub fn translate(&mut self, (dx, dy): (f64, f64)) {
+        for point in self.points_mut() {
+            point.0 += dx;
+            point.1 += dy;
+        }
+    }
+
+    /// 以 anchor 为不动点缩放标注，文字的字号按纵向比例缩放
+    pub fn scale(&mut self, anchor: (f64, f64), (sx, sy): (f64, f64)) {
+        for point in self.points_mut() {
+            point.0 = anchor.0 + (point.0 - anchor.0) * sx;
+            point.1 = anchor.1 + (point.1 - anchor.1) * sy;
+        }
+        if let Shape::Text { size, .. } | Shape::Bubble { size, .. } = &mut self.shape {
+            *size = (*size * sy.abs()).max(MIN_FONT_SIZE);
+        }
+    }
+
+    /// 以 anchor 为不动点等比缩放标注，线宽（及随之变化的步骤标记半径）和字号一同缩放
+    pub fn zoom(&mut self, anchor: (f64, f64), factor: f64) {
+        self.scale(anchor, (factor, factor));
+        self.width *= factor;
+    }
+
+    /// 没有拖动出大小或没有文字的标注，结束时丢弃
+    pub fn is_empty(&self) -> bool {
+        match &self.shape {
+            Shape::Rectangle { start, end }
+            | Shape::Ellipse { start, end }
+            | Shape::Line { start, end }
+            | Shape::Arrow { start, end }
+            | Shape::Redact { start, end, .. } => {
+                (end.0 - start.0).abs() < 2.0 && (end.1 - start.1).abs() < 2.0
+            }
+            Shape::Text { text, .. } | Shape::Bubble { text, .. } => text.trim().is_empty(),
+            Shape::Pen { points } | Shape::Highlight { points } => points.len() < 2,
+            Shape::Step { .. } => false,
+        }
+    }
+
+    /// 在 ctx 的当前坐标系（全局逻辑坐标）中绘制，打码从 backdrop 的原始像素取样
+    pub fn draw(&self, ctx: &Context, backdrop: Option<&Backdrop>) {
+        ctx.save().unwrap();
+        self.color.set_source(ctx);
+        ctx.set_line_width(self.width);
+        ctx.set_line_join(LineJoin::Round);
+        ctx.set_line_cap(LineCap::Round);
+        match &self.shape {
+            &Shape::Rectangle { start, end } => {
+                ctx.rectangle(start.0, start.1, end.0 - start.0, end.1 - start.1);
+                ctx.stroke().unwrap();
+            }
+            &Shape::Ellipse { start, end } => {
+                let (rx, ry) = ((end.0 - start.0).abs() / 2.0, (end.1 - start.1).abs() / 2.0);
+                if rx > 0.0 && ry > 0.0 {
+                    // NOTE: 先缩放画出路径，恢复后再描边，避免线宽被缩放
+                    ctx.save().unwrap();
+                    ctx.translate((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0);
+                    ctx.scale(rx, ry);
+                    ctx.arc(0.0, 0.0, 1.0, 0.0, 2.0 * PI);
+                    ctx.restore().unwrap();
+                    ctx.stroke().unwrap();
+                }
+            }
+            &Shape::Line { start, end } => {
+                ctx.move_to(start.0, start.1);
+                ctx.line_to(end.0, end.1);
+                ctx.stroke().unwrap();
+            }
+            &Shape::Arrow { start, end } => draw_arrow(ctx, start, end, self.width),
+            Shape::Pen { points } => {
+                smooth_path(ctx, points);
+                ctx.stroke().unwrap();
+            }
+            Shape::Highlight { points } => {
+                // NOTE: 正片叠底只会让底色变暗，下方的文字仍然清晰
+                let Color { r, g, b, a } = self.color;
+                ctx.set_source_rgba(r, g, b, a * HIGHLIGHT_ALPHA);
+                ctx.set_operator(Operator::Multiply);
+                ctx.set_line_width(self.width * HIGHLIGHT_SCALE);
+                smooth_path(ctx, points);
+                ctx.stroke().unwrap();
+            }
+            Shape::Text {
+                pos,
+                text,
+                font,
+                size,
+            } => draw_lines(ctx, *pos, text, font, *size),
+            &Shape::Step { center, number } => {
+                let radius = self.step_radius();
+                ctx.arc(center.0, center.1, radius, 0.0, 2.0 * PI);
+                ctx.fill().unwrap();
+                // NOTE: 数字使用白色，与任意标注颜色都有对比
+                ctx.set_source_rgb(1.0, 1.0, 1.0);
+                ctx.select_font_face("Sans", FontSlant::Normal, FontWeight::Bold);
+                ctx.set_font_size(radius * 1.1);
+                let label = number.to_string();
+                let extents = ctx.text_extents(&label).unwrap();
+                ctx.move_to(
+                    center.0 - extents.width() / 2.0 - extents.x_bearing(),
+                    center.1 - extents.height() / 2.0 - extents.y_bearing(),
+                );
+                ctx.show_text(&label).unwrap();
+            }
+            Shape::Bubble {
+                tip,
+                pos,
+                text,
+                font,
+                size,
+            } => {
+                let (origin, rect) = bubble_layout(ctx, *pos, text, font, *size);
+                let (bx, by, bw, bh) = rect;
+                let center = (bx + bw / 2.0, by + bh / 2.0);
+                let (dx, dy) = (tip.0 - center.0, tip.1 - center.1);
+                let length = dx.hypot(dy);
+                let half = (bw.min(bh) / 4.0).min(12.0);
+                let path = |ctx: &Context| {
+                    rounded_rect(ctx, rect, (bh / 2.0).min(12.0));
+                    // NOTE: 尾巴从气泡中心出发，被气泡遮住的部分不可见
+                    if length > 0.0 {
+                        let (nx, ny) = (-dy / length * half, dx / length * half);
+                        ctx.move_to(center.0 + nx, center.1 + ny);
+                        ctx.line_to(tip.0, tip.1);
+                        ctx.line_to(center.0 - nx, center.1 - ny);
+                        ctx.close_path();
+                    }
+                };
+                // NOTE: 先描边再填充，重叠处的内侧边框被填充盖住，只留下外轮廓
+                path(ctx);
+                ctx.set_line_width(self.width.max(1.0) * 2.0);
+                ctx.stroke().unwrap();
+                path(ctx);
+                ctx.set_source_rgb(1.0, 1.0, 1.0);
+                ctx.fill().unwrap();
+                self.color.set_source(ctx);
+                draw_lines(ctx, origin, text, font, *size);
+            }
+            &Shape::Redact {
+                start,
+                end,
+                redaction,
+            } => {
+                let rect = (
+                    start.0.min(end.0),
+                    start.1.min(end.1),
+                    (end.0 - start.0).abs(),
+                    (end.1 - start.1).abs(),
+                );
+                match backdrop {
+                    Some(backdrop) => backdrop.redact(ctx, rect, redaction),
+                    None => {
+                        // NOTE: 没有原始像素时整块涂黑
+                        ctx.set_source_rgb(0.0, 0.0, 0.0);
+                        ctx.rectangle(rect.0, rect.1, rect.2, rect.3);
+                        ctx.fill().unwrap();
+                    }
+                }
+            }
+        }
+        ctx.restore().unwrap();
+    }
+
+    /// 文本标注中 caret（字节下标）处光标的位置和高度
+    pub fn caret(&self, ctx: &Context, caret: usize) -> Option<((f64, f64), f64)> {
+        let (pos, text, font, size) = match &self.shape {
+            Shape::Text {
+                pos,
+                text,
+                font,
+                size,
+            } => (*pos, text, font, *size),
+            Shape::Bubble {
+                pos,
+                text,
+                font,
+                size,
+                ..
+            } => (
+                bubble_layout(ctx, *pos, text, font, *size).0,
+                text,
+                font,
+                *size,
+            ),
+            _ => return None,
+        };
+        ctx.save().unwrap();
+        set_font(ctx, font, size);
+        let extents = ctx.font_extents().unwrap();
+        let before = &text[..caret];
+        let line = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let x = ctx.text_extents(&before[line_start..]).unwrap().x_advance();
+        ctx.restore().unwrap();
+        Some((
+            (pos.0 + x, pos.1 + line as f64 * extents.height()),
+            extents.ascent() + extents.descent(),
+        ))
+    }
+}
+
+/// 使用 cairo 的字体 API 选择字体
+fn set_font(ctx: &Context, font: &str, size: f64) {
+    ctx.select_font_face(font, FontSlant::Normal, FontWeight::Normal);
+    ctx.set_font_size(size);
+}
+
+/// 经过各点中点的二次曲线，使画笔轨迹平滑
+fn smooth_path(ctx: &Context, points: &[(f64, f64)]) {
+    let Some((&first, rest)) = points.split_first() else {
+        return;
+    };
+    ctx.move_to(first.0, first.1);
+    let mut current = first;
+    for pair in rest.windows(2) {
+        let (control, next) = (pair[0], pair[1]);
+        let end = ((control.0 + next.0) / 2.0, (control.1 + next.1) / 2.0);
+        // NOTE: cairo 只有三次曲线，二次曲线的控制点按 2/3 换算
+        ctx.curve_to(
+            current.0 + (control.0 - current.0) * 2.0 / 3.0,
+            current.1 + (control.1 - current.1) * 2.0 / 3.0,
+            end.0 + (control.0 - end.0) * 2.0 / 3.0,
+            end.1 + (control.1 - end.1) * 2.0 / 3.0,
+            end.0,
+            end.1,
+        );
+        current = end;
+    }
+    if let Some(last) = rest.last() {
+        ctx.line_to(last.0, last.1);
+    }
+}
+
+/// 以 pos 为左上角逐行绘制文字
+fn draw_lines(ctx: &Context, pos: (f64, f64), text: &str, font: &str, size: f64) {
+    ctx.save().unwrap();
+    set_font(ctx, font, size);
+    let extents = ctx.font_extents().unwrap();
+    for (i, line) in text.split('\n').enumerate() {
+        ctx.move_to(
+            pos.0,
+            pos.1 + extents.ascent() + i as f64 * extents.height(),
+        );
+        ctx.show_text(line).unwrap();
+    }
+    ctx.restore().unwrap();
+}
+
+/// 多行文字的宽高
+fn text_size(ctx: &Context, text: &str, font: &str, size: f64) -> (f64, f64) {
+    ctx.save().unwrap();
+    set_font(ctx, font, size);
+    let extents = ctx.font_extents().unwrap();
+    let width = text
+        .split('\n')
+        .map(|line| ctx.text_extents(line).unwrap().x_advance())
+        .fold(0.0, f64::max);
+    let lines = text.split('\n').count() as f64;
+    let height = extents.height() * (lines - 1.0) + extents.ascent() + extents.descent();
+    ctx.restore().unwrap();
+    (width, height)
+}
+
+/// 不在任何 surface 上绘制时用于测量文字
+fn scratch_context() -> Context {
+    let surface = ImageSurface::create(cairo::Format::ARgb32, 1, 1).unwrap();
+    Context::new(&surface).unwrap()
+}
+
+/// 扩展矩形以包含 point
+fn union((x, y, w, h): (f64, f64, f64, f64), point: (f64, f64)) -> (f64, f64, f64, f64) {
+    let (left, top) = (x.min(point.0), y.min(point.1));
+    let (right, bottom) = ((x + w).max(point.0), (y + h).max(point.1));
+    (left, top, right - left, bottom - top)
+}
+
+/// 点到线段的距离
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+    };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// 以 pos 为中心的气泡中文字的左上角和气泡的矩形，气泡随文字增大
+fn bubble_layout(
+    ctx: &Context,
+    pos: (f64, f64),
+    text: &str,
+    font: &str,
+    size: f64,
+) -> ((f64, f64), (f64, f64, f64, f64)) {
+    let (width, height) = text_size(ctx, text, font, size);
+    // NOTE: 没有文字时保留一个字号宽度，气泡不会缩成一条线
+    let width = width.max(size);
+    let padding = size / 2.0;
+    let origin = (pos.0 - width / 2.0, pos.1 - height / 2.0);
+    let rect = (
+        origin.0 - padding,
+        origin.1 - padding,
+        width + padding * 2.0,
+        height + padding * 2.0,
+    );
+    (origin, rect)
+}
+
+pub fn rounded_rect(ctx: &Context, (x, y, w, h): (f64, f64, f64, f64), r: f64) {
+    ctx.new_sub_path();
+    ctx.arc(x + w - r, y + r, r, -PI / 2.0, 0.0);
+    ctx.arc(x + w - r, y + h - r, r, 0.0, PI / 2.0);
+    ctx.arc(x + r, y + h - r, r, PI / 2.0, PI);
+    ctx.arc(x + r, y + r, r, PI, 3.0 * PI / 2.0);
+    ctx.close_path();
+}
+
+/// 箭头的线段止于箭头底部，箭头大小随线宽变化
+fn draw_arrow(ctx: &Context, start: (f64, f64), end: (f64, f64), width: f64) {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length = dx.hypot(dy);
+    if length == 0.0 {
+        return;
+    }
+    let (ux, uy) = (dx / length, dy / length);
+    let head = (width * 4.0).max(10.0).min(length);
+    let base = (end.0 - ux * head, end.1 - uy * head);
+    let half = head / 2.0;
+
+    ctx.move_to(start.0, start.1);
+    ctx.line_to(base.0, base.1);
+    ctx.stroke().unwrap();
+
+    ctx.move_to(end.0, end.1);
+    ctx.line_to(base.0 - uy * half, base.1 + ux * half);
+    ctx.line_to(base.0 + uy * half, base.1 - ux * half);
+    ctx.close_path();
+    ctx.fill().unwrap();
+}
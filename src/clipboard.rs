@@ -0,0 +1,206 @@
+use std::io::Write;
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use cairo::ImageSurface;
+use log::{debug, error, info};
+
+use crate::encoder::ImageFormat;
+use crate::wayland_ctx::WaylandCtx;
+
+/// 除截图格式外，额外提供给剪贴板的 mime 类型
+pub const MIME_TYPES: [&str; 6] = [
+    "image/png",
+    "image/jpeg",
+    "text/uri-list",
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+];
+
+/// 纯文本内容提供的 mime 类型
+const TEXT_MIME_TYPES: [&str; 3] = ["text/plain;charset=utf-8", "text/plain", "UTF8_STRING"];
+
+/// 剪贴板内容
+pub enum Clipboard {
+    /// 截图
+    Image {
+        /// 与保存的文件相同的编码数据
+        encoded: Vec<u8>,
+        format: ImageFormat,
+        image: ImageSurface,
+        /// 保存的文件路径，输出到 stdout 时为 None
+        path: Option<PathBuf>,
+        /// 其他有损格式的质量
+        quality: u8,
+    },
+    /// 纯文本，如取色的结果
+    Text(String),
+}
+
+impl Clipboard {
+    /// 截图的剪贴板内容
+    pub fn new(
+        encoded: Vec<u8>,
+        format: ImageFormat,
+        image: ImageSurface,
+        path: Option<&Path>,
+        quality: u8,
+    ) -> Self {
+        Self::Image {
+            encoded,
+            format,
+            image,
+            // NOTE: uri-list 需要绝对路径
+            path: path.map(|p| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf())),
+            quality,
+        }
+    }
+
+    /// 创建数据源并设置为剪贴板选择，成功返回 true，失败时不需要 fork 出进程提供剪贴板。
+    /// 优先使用 wlr data control，不需要键盘焦点
+    pub fn offer(&self, wl_ctx: &WaylandCtx) -> bool {
+        let (Some(seat), Some(qh)) = (wl_ctx.seat.as_ref(), wl_ctx.qh.as_ref()) else {
+            error!("no seat to set clipboard selection");
+            return false;
+        };
+
+        if let Some(manager) = wl_ctx.data_control_manager.as_ref() {
+            let source = manager.create_data_source(qh, ());
+            for mime in self.mime_types() {
+                source.offer(mime.to_string());
+            }
+            let device = manager.get_data_device(seat, qh, ());
+            device.set_selection(Some(&source));
+            info!("clipboard set by data control");
+            return true;
+        }
+
+        let Some(manager) = wl_ctx.data_device_manager.as_ref() else {
+            error!("no data device manager to set clipboard selection, not copying");
+            return false;
+        };
+        // NOTE: wl_data_device 需要输入事件的 serial，--full-screen 等没有输入时无法设置剪贴板
+        let Some(serial) = wl_ctx.serial else {
+            error!(
+                "no input event to set clipboard selection without wlr data control, not copying"
+            );
+            return false;
+        };
+        let source = manager.create_data_source(qh, ());
+        for mime in self.mime_types() {
+            source.offer(mime.to_string());
+        }
+        let device = manager.get_data_device(seat, qh, ());
+        device.set_selection(Some(&source), serial);
+        info!("clipboard set by data device");
+        true
+    }
+
+    /// 截图格式的 mime 类型在前，接收方通常选择第一个支持的类型；没有文件时不提供路径
+    fn mime_types(&self) -> Vec<&'static str> {
+        let Self::Image { format, path, .. } = self else {
+            return TEXT_MIME_TYPES.to_vec();
+        };
+        let mut mimes = vec![format.mime_type()];
+        mimes.extend(
+            MIME_TYPES
+                .into_iter()
+                .filter(|&m| m != format.mime_type())
+                .filter(|m| path.is_some() || m.starts_with("image/")),
+        );
+        mimes
+    }
+
+    /// 按 mime 类型生成数据
+    pub fn data(&self, mime_type: &str) -> Option<Vec<u8>> {
+        let (encoded, format, image, path, quality) = match self {
+            Self::Image {
+                encoded,
+                format,
+                image,
+                path,
+                quality,
+            } => (encoded, *format, image, path, *quality),
+            Self::Text(text) => {
+                return TEXT_MIME_TYPES
+                    .contains(&mime_type)
+                    .then(|| text.clone().into_bytes());
+            }
+        };
+        if mime_type == format.mime_type() {
+            return Some(encoded.clone());
+        }
+        match mime_type {
+            "image/png" => encode(image, ImageFormat::Png, quality),
+            "image/jpeg" => encode(image, ImageFormat::Jpeg, quality),
+            "text/uri-list" => Some(format!("{}\r\n", file_uri(path.as_ref()?)).into_bytes()),
+            "text/plain;charset=utf-8" | "text/plain" | "UTF8_STRING" => {
+                Some(path.as_ref()?.display().to_string().into_bytes())
+            }
+            _ => None,
+        }
+    }
+
+    /// 将数据写入请求方提供的 fd
+    pub fn send(&self, mime_type: &str, fd: OwnedFd) {
+        debug!("clipboard send {}", mime_type);
+        let Some(data) = self.data(mime_type) else {
+            error!("unsupported mime type {}", mime_type);
+            return;
+        };
+        let mut file = std::fs::File::from(fd);
+        if let Err(e) = file.write_all(&data) {
+            error!("failed to write clipboard data: {}", e);
+        }
+    }
+}
+
+/// 按 RFC 8089 生成 file URI，路径的字节除非保留字符和 `/` 外都百分号编码
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+fn encode(image: &ImageSurface, format: ImageFormat, quality: u8) -> Option<Vec<u8>> {
+    format
+        .encode(image, quality)
+        .map_err(|e| error!("{}", e))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    #[test]
+    fn uri_escaping() {
+        assert_eq!(file_uri(Path::new("/tmp/shot.png")), "file:///tmp/shot.png");
+        assert_eq!(
+            file_uri(Path::new("/tmp/my shot.png")),
+            "file:///tmp/my%20shot.png"
+        );
+        assert_eq!(
+            file_uri(Path::new("/tmp/100%#1.png")),
+            "file:///tmp/100%25%231.png"
+        );
+        assert_eq!(
+            file_uri(Path::new("/tmp/截图.png")),
+            "file:///tmp/%E6%88%AA%E5%9B%BE.png"
+        );
+        assert_eq!(
+            file_uri(Path::new(OsStr::from_bytes(b"/tmp/\xff.png"))),
+            "file:///tmp/%FF.png"
+        );
+    }
+}
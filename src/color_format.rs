@@ -0,0 +1,138 @@
+/// 取色结果的输出格式。
+/// 预设 hex（#rrggbb）、rgb（rgb(r, g, b)）和 hsl（hsl(h, s%, l%)），其他字符串作为模板，支持：
+/// %r/%g/%b 十进制通道，%x 六位十六进制，%X 大写的六位十六进制，
+/// %h/%s/%l 色相（度）、饱和度和亮度（百分比），%% 为 `%`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorFormat(String);
+
+impl Default for ColorFormat {
+    fn default() -> Self {
+        Self("hex".to_string())
+    }
+}
+
+impl ColorFormat {
+    /// 创建格式，模板中有未知的记号时返回错误
+    pub fn new(format: String) -> Result<Self, String> {
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c == '%'
+                && !matches!(
+                    chars.next(),
+                    Some('r' | 'g' | 'b' | 'x' | 'X' | 'h' | 's' | 'l' | '%')
+                )
+            {
+                return Err(format!(
+                    "invalid color format `{}`, use hex, rgb, hsl or a template with %r %g %b %x %X %h %s %l",
+                    format
+                ));
+            }
+        }
+        Ok(Self(format))
+    }
+
+    pub fn format(&self, (r, g, b): (u8, u8, u8)) -> String {
+        let template = match self.0.as_str() {
+            "hex" => "#%x",
+            "rgb" => "rgb(%r, %g, %b)",
+            "hsl" => "hsl(%h, %s%%, %l%%)",
+            template => template,
+        };
+        let (h, s, l) = hsl((r, g, b));
+        let mut result = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('r') => result.push_str(&r.to_string()),
+                Some('g') => result.push_str(&g.to_string()),
+                Some('b') => result.push_str(&b.to_string()),
+                Some('x') => result.push_str(&format!("{:02x}{:02x}{:02x}", r, g, b)),
+                Some('X') => result.push_str(&format!("{:02X}{:02X}{:02X}", r, g, b)),
+                Some('h') => result.push_str(&h.to_string()),
+                Some('s') => result.push_str(&s.to_string()),
+                Some('l') => result.push_str(&l.to_string()),
+                Some(other) => result.push(other),
+                None => result.push('%'),
+            }
+        }
+        result
+    }
+}
+
+/// RGB 转换为 HSL，色相为 0-359 度，饱和度和亮度为 0-100
+fn hsl((r, g, b): (u8, u8, u8)) -> (u32, u32, u32) {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0, 0, (l * 100.0).round() as u32);
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (
+        ((h * 60.0).round() as u32) % 360,
+        (s * 100.0).round() as u32,
+        (l * 100.0).round() as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(template: &str, rgb: (u8, u8, u8)) -> String {
+        ColorFormat::new(template.to_string()).unwrap().format(rgb)
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(format("hex", (255, 128, 0)), "#ff8000");
+        assert_eq!(format("rgb", (255, 128, 0)), "rgb(255, 128, 0)");
+        assert_eq!(format("hsl", (255, 128, 0)), "hsl(30, 100%, 50%)");
+    }
+
+    #[test]
+    fn template() {
+        assert_eq!(format("%X", (171, 205, 239)), "ABCDEF");
+        assert_eq!(format("%r/%g/%b", (1, 2, 3)), "1/2/3");
+        assert_eq!(format("100%%", (0, 0, 0)), "100%");
+        // NOTE: new 拒绝末尾单独的 %，format 仍原样输出
+        let trailing = ColorFormat("%x%".to_string());
+        assert_eq!(trailing.format((0, 0, 0)), "000000%");
+    }
+
+    #[test]
+    fn invalid_template() {
+        assert!(ColorFormat::new("%q".to_string()).is_err());
+        assert!(ColorFormat::new("%".to_string()).is_err());
+    }
+
+    #[test]
+    fn hsl_grey() {
+        assert_eq!(hsl((0, 0, 0)), (0, 0, 0));
+        assert_eq!(hsl((128, 128, 128)), (0, 0, 50));
+        assert_eq!(hsl((255, 255, 255)), (0, 0, 100));
+    }
+
+    #[test]
+    fn hsl_hue() {
+        assert_eq!(hsl((255, 0, 0)), (0, 100, 50));
+        assert_eq!(hsl((0, 255, 0)), (120, 100, 50));
+        assert_eq!(hsl((0, 0, 255)), (240, 100, 50));
+        // NOTE: 接近 360° 的色相回绕到 0°
+        assert_eq!(hsl((255, 0, 1)), (0, 100, 50));
+        assert_eq!(hsl((255, 0, 128)), (330, 100, 50));
+    }
+}
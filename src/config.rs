@@ -1,38 +1,500 @@
-use chrono::Local;
-use clap::Parser;
-use directories::UserDirs;
-use log::info;
-use std::path::PathBuf; // 引入 chrono 库用于时间处理
+use clap::{Parser, Subcommand};
+use directories::{ProjectDirs, UserDirs};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf}; // 引入 chrono 库用于时间处理
+use std::str::FromStr;
+use xkbcommon::xkb;
+
+use crate::color_format::ColorFormat;
+use crate::encoder::ImageFormat;
+use crate::path_template::PathTemplate;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct CliArgs {
     /// show cursor when screen freeze, default to false
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, overrides_with = "hide_cursor")]
     show_cursor: bool,
 
-    /// output path, default to xdg user picture dir, supports format specifiers like %Y, %m, %d, %H, %M, %S
+    /// hide cursor when screen freeze, overriding `show_cursor = true` in the config file
+    #[arg(long, default_value_t = false, overrides_with = "show_cursor")]
+    hide_cursor: bool,
+
+    /// output path template, default to xdg user picture dir, supports strftime specifiers, %n counter, %o output name, %w/%h selection size, %K capture mode, `-` for stdout
     #[arg(short, long)]
     output_path: Option<PathBuf>,
 
+    /// image format, inferred from the output path extension when omitted, default to png
+    #[arg(short, long, value_enum)]
+    format: Option<ImageFormat>,
+
     /// disable quickshot, default to true
-    #[arg(long = "no-quickshot")]
+    #[arg(long = "no-quickshot", overrides_with = "quickshot")]
     no_quickshot: bool,
 
-    #[arg(long, default_value_t = false)]
+    /// enable quickshot, overriding `quickshot = false` in the config file
+    #[arg(long, default_value_t = false, overrides_with = "no_quickshot")]
+    quickshot: bool,
+
+    /// adjust the selection with handles after releasing the mouse, Enter confirms and Escape cancels
+    #[arg(long, default_value_t = false, overrides_with = "no_adjust")]
+    adjust: bool,
+
+    /// capture right after releasing the mouse, overriding `adjust = true` in the config file
+    #[arg(long, default_value_t = false, overrides_with = "adjust")]
+    no_adjust: bool,
+
+    /// do not snap the selection to window borders and strong edges in the image, holding Ctrl toggles snapping while dragging
+    #[arg(long, default_value_t = false, overrides_with = "snap")]
+    no_snap: bool,
+
+    /// snap the selection, overriding `snap = false` in the config file
+    #[arg(long, default_value_t = false, overrides_with = "no_snap")]
+    snap: bool,
+
+    /// hide the magnifier that shows the pixels, coordinates and colour under the cursor while selecting
+    #[arg(long, default_value_t = false, overrides_with = "loupe")]
+    no_loupe: bool,
+
+    /// show the magnifier, overriding `loupe = false` in the config file
+    #[arg(long, default_value_t = false, overrides_with = "no_loupe")]
+    loupe: bool,
+
+    /// do not copy the screenshot to the clipboard
+    #[arg(long, default_value_t = false, overrides_with = "copy")]
     no_copy: bool,
 
+    /// copy the screenshot to the clipboard, overriding `copy = false` in the config file
+    #[arg(long, default_value_t = false, overrides_with = "no_copy")]
+    copy: bool,
+
     #[arg(long, default_value_t = false)]
     full_screen: bool,
+
+    /// output to capture in full screen mode, an output name like DP-1, or `focused` for the output under the pointer, requires --full-screen
+    #[arg(long, value_name = "NAME", requires = "full_screen")]
+    output: Option<String>,
+
+    /// config file, default to $XDG_CONFIG_HOME/foamshot/config.toml
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// capture profile defined in the config file
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// also save a project file with the original capture and the annotations, reopen it with `foamshot edit`
+    #[arg(long, default_value_t = false, overrides_with = "no_project")]
+    project: bool,
+
+    /// do not save a project file, overriding `project = true` in the config file
+    #[arg(long, default_value_t = false, overrides_with = "project")]
+    no_project: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// reopen a project file saved with --project in the editor
+    Edit {
+        /// the .foamshot.toml project file
+        file: PathBuf,
+    },
+    /// measure distances and sizes on the frozen screen, drag for a line and Alt-drag for a rectangle
+    Measure,
+    /// click a pixel on the frozen screen, print its colour and copy it to the clipboard
+    PickColor {
+        /// hex, rgb, hsl, or a template with %r %g %b %x %X %h %s %l, default to hex
+        #[arg(long, value_name = "FORMAT")]
+        format: Option<String>,
+    },
+}
+
+/// 全屏截图的目标输出
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputTarget {
+    /// 按名称或描述匹配的输出
+    Named(String),
+    /// 鼠标所在的输出
+    Focused,
+}
+
+impl From<String> for OutputTarget {
+    fn from(name: String) -> Self {
+        if name == "focused" {
+            OutputTarget::Focused
+        } else {
+            OutputTarget::Named(name)
+        }
+    }
+}
+
+/// RGBA 颜色，配置文件中写作 `#rrggbb` 或 `#rrggbbaa`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Color {
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self {
+            r: r as f64 / 255.0,
+            g: g as f64 / 255.0,
+            b: b as f64 / 255.0,
+            a: a as f64 / 255.0,
+        }
+    }
+
+    /// 设置为 cairo 的绘制颜色
+    pub fn set_source(&self, ctx: &cairo::Context) {
+        ctx.set_source_rgba(self.r, self.g, self.b, self.a);
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix('#')
+            .ok_or_else(|| format!("color `{}` must start with `#`", s))?;
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| format!("invalid color `{}`, expected #rrggbb or #rrggbbaa", s))
+        };
+        match hex.len() {
+            6 => Ok(Color::rgba(channel(0)?, channel(2)?, channel(4)?, 255)),
+            8 => Ok(Color::rgba(
+                channel(0)?,
+                channel(2)?,
+                channel(4)?,
+                channel(6)?,
+            )),
+            _ => Err(format!(
+                "invalid color `{}`, expected #rrggbb or #rrggbbaa",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        let channel = |c: f64| (c * 255.0).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            channel(color.r),
+            channel(color.g),
+            channel(color.b),
+            channel(color.a)
+        )
+    }
+}
+
+/// 选择层的颜色
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    /// 选区外的遮罩
+    pub overlay: Color,
+    /// 选区尺寸标签
+    pub label: Color,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            overlay: Color::rgba(255, 255, 255, 77),
+            label: Color::rgba(0, 0, 0, 255),
+        }
+    }
+}
+
+/// 标注编辑器
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Editor {
+    /// 默认颜色
+    pub color: Color,
+    /// 默认线宽，逻辑像素
+    pub width: f64,
+    /// 工具栏中可选的颜色
+    pub palette: Vec<Color>,
+    /// 工具栏中可选的线宽
+    pub widths: Vec<f64>,
+    /// 文本的默认字体族
+    pub font: String,
+    /// 文本的默认字号，逻辑像素
+    pub font_size: f64,
+    /// 工具栏中可选的字体族
+    pub fonts: Vec<String>,
+    /// 工具栏中可选的字号
+    pub font_sizes: Vec<f64>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            color: Color::rgba(0xe5, 0x39, 0x35, 255),
+            width: 4.0,
+            palette: vec![
+                Color::rgba(0xe5, 0x39, 0x35, 255),
+                Color::rgba(0xfb, 0x8c, 0x00, 255),
+                Color::rgba(0xfd, 0xd8, 0x35, 255),
+                Color::rgba(0x43, 0xa0, 0x47, 255),
+                Color::rgba(0x1e, 0x88, 0xe5, 255),
+                Color::rgba(0, 0, 0, 255),
+                Color::rgba(255, 255, 255, 255),
+            ],
+            widths: vec![2.0, 4.0, 8.0],
+            font: "Sans".to_string(),
+            font_size: 20.0,
+            fonts: vec![
+                "Sans".to_string(),
+                "Serif".to_string(),
+                "Monospace".to_string(),
+            ],
+            font_sizes: vec![14.0, 20.0, 32.0],
+        }
+    }
+}
+
+/// 按 xkb keysym 名称绑定的按键，如 `a`、`Escape`、`Return`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Key(pub xkb::Keysym);
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let keysym = xkb::keysym_from_name(&name, xkb::KEYSYM_NO_FLAGS);
+        if keysym.raw() == xkb::keysyms::KEY_NoSymbol {
+            Err(format!("unknown key `{}`", name))
+        } else {
+            Ok(Key(keysym))
+        }
+    }
+}
+
+/// 按键绑定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    /// 等待框选时截取鼠标所在的整个输出
+    pub full_screen: Key,
+    /// 等待框选时退出
+    pub exit: Key,
+    /// 查看结果时返回重新框选
+    pub back: Key,
+    /// 编辑时保存
+    pub confirm: Key,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            full_screen: Key(xkb::Keysym::a),
+            exit: Key(xkb::Keysym::Escape),
+            back: Key(xkb::Keysym::Escape),
+            confirm: Key(xkb::Keysym::Return),
+        }
+    }
+}
+
+/// 截图配置，配置文件顶层和每个 profile 中都可以设置
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Profile {
+    /// 完整的输出路径模板，优先于 output_dir 和 filename
+    output_path: Option<String>,
+    output_dir: Option<String>,
+    filename: Option<String>,
+    format: Option<ImageFormat>,
+    /// 有损格式的质量，1-100
+    quality: Option<u8>,
+    show_cursor: Option<bool>,
+    quickshot: Option<bool>,
+    /// 松开鼠标后调整选区，确认后才截图
+    adjust: Option<bool>,
+    /// 框选时显示放大镜
+    loupe: Option<bool>,
+    /// 框选时吸附到窗口和画面边缘
+    snap: Option<bool>,
+    copy: Option<bool>,
+    /// 同时保存工程文件
+    project: Option<bool>,
+    /// 保存后执行的 shell 命令，截图路径在环境变量 FOAMSHOT_PATH 中
+    hooks: Option<Vec<String>>,
+}
+
+impl Profile {
+    /// 以 self 覆盖 base
+    fn merge(self, base: Profile) -> Profile {
+        // NOTE: profile 指定了目录或文件名时，不再使用 base 的完整路径
+        let output_path = if self.output_dir.is_some() || self.filename.is_some() {
+            self.output_path
+        } else {
+            self.output_path.or(base.output_path)
+        };
+        Profile {
+            output_path,
+            output_dir: self.output_dir.or(base.output_dir),
+            filename: self.filename.or(base.filename),
+            format: self.format.or(base.format),
+            quality: self.quality.or(base.quality),
+            show_cursor: self.show_cursor.or(base.show_cursor),
+            quickshot: self.quickshot.or(base.quickshot),
+            adjust: self.adjust.or(base.adjust),
+            loupe: self.loupe.or(base.loupe),
+            snap: self.snap.or(base.snap),
+            copy: self.copy.or(base.copy),
+            project: self.project.or(base.project),
+            hooks: self.hooks.or(base.hooks),
+        }
+    }
+}
+
+/// 配置文件 $XDG_CONFIG_HOME/foamshot/config.toml，命令行参数优先
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    output_path: Option<String>,
+    output_dir: Option<String>,
+    filename: Option<String>,
+    format: Option<ImageFormat>,
+    quality: Option<u8>,
+    show_cursor: Option<bool>,
+    quickshot: Option<bool>,
+    adjust: Option<bool>,
+    loupe: Option<bool>,
+    snap: Option<bool>,
+    copy: Option<bool>,
+    project: Option<bool>,
+    hooks: Option<Vec<String>>,
+    /// foamshot pick-color 的输出格式
+    color_format: Option<String>,
+    colors: Colors,
+    keys: KeyBindings,
+    editor: Editor,
+    /// 按名称选择的 profile，通过 --profile 使用
+    profiles: HashMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// 读取配置文件，未指定路径且默认文件不存在时使用默认配置，文件无效时报错退出
+    fn load(path: Option<PathBuf>) -> Self {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => match ProjectDirs::from("", "", "foamshot") {
+                Some(dirs) => (dirs.config_dir().join("config.toml"), false),
+                None => return Self::default(),
+            },
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Self::default();
+            }
+            Err(e) => {
+                error!("failed to read config file {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+        info!("config file: {}", path.display());
+
+        // NOTE: toml 的错误信息包含出错的行列和对应的文本
+        toml::from_str(&content).unwrap_or_else(|e| {
+            error!("invalid config file {}:\n{}", path.display(), e);
+            std::process::exit(1);
+        })
+    }
+    /// 顶层的截图配置，叠加指定的 profile，profile 不存在时报错退出
+    fn resolve(&mut self, profile: Option<&str>) -> Profile {
+        let base = Profile {
+            output_path: self.output_path.take(),
+            output_dir: self.output_dir.take(),
+            filename: self.filename.take(),
+            format: self.format,
+            quality: self.quality,
+            show_cursor: self.show_cursor,
+            quickshot: self.quickshot,
+            adjust: self.adjust,
+            loupe: self.loupe,
+            snap: self.snap,
+            copy: self.copy,
+            project: self.project,
+            hooks: self.hooks.take(),
+        };
+        let Some(name) = profile else {
+            return base;
+        };
+        match self.profiles.remove(name) {
+            Some(profile) => {
+                info!("profile: {}", name);
+                profile.merge(base)
+            }
+            None => {
+                let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                names.sort();
+                error!(
+                    "profile {} not found, available: {}",
+                    name,
+                    names.join(", ")
+                );
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Cli {
     pub no_cursor: bool,
-    pub output_path: PathBuf,
+    pub output_path: PathTemplate,
     pub quickshot: bool,
+    /// 松开鼠标后调整选区，确认后才截图
+    pub adjust: bool,
+    /// 框选时显示放大镜
+    pub loupe: bool,
+    /// 框选时吸附到窗口和画面边缘
+    pub snap: bool,
     pub auto_copy: bool,
     pub full_screen: bool,
+    pub output: Option<OutputTarget>,
+    pub format: ImageFormat,
+    pub quality: u8,
+    /// 同时保存工程文件
+    pub project: bool,
+    /// foamshot edit 打开的工程文件
+    pub edit: Option<PathBuf>,
+    /// foamshot pick-color 取色而不截图
+    pub pick_color: bool,
+    pub color_format: ColorFormat,
+    /// foamshot measure 测量而不截图
+    pub measure: bool,
+    pub hooks: Vec<String>,
+    pub colors: Colors,
+    pub keys: KeyBindings,
+    pub editor: Editor,
 }
 
 impl Default for Cli {
@@ -44,54 +506,174 @@ impl Default for Cli {
 impl Cli {
     pub fn new() -> Self {
         let args = CliArgs::parse();
+        let mut file = ConfigFile::load(args.config);
+        let settings = file.resolve(args.profile.as_deref());
+        let cli_path = args.output_path.map(|p| p.to_string_lossy().to_string());
+        let format = resolve_format(
+            args.format,
+            cli_path.as_deref(),
+            settings.format,
+            settings
+                .output_path
+                .as_deref()
+                .or(settings.filename.as_deref()),
+        );
+        info!("image format: {:?}", format);
+        let output_template = cli_path.or(settings.output_path);
+
+        // 未提供时由目录和文件名生成默认模板
+        let output_template = output_template.unwrap_or_else(|| {
+            Self::default_output_template(settings.output_dir, settings.filename, format)
+        });
+        // NOTE: 扩展名与格式不一致时改为格式的扩展名，避免 .png 文件中实际是 JPEG
+        let output_template = match ImageFormat::from_path(Path::new(&output_template)) {
+            Some(ext) if ext.extension() != format.extension() => {
+                let fixed = Path::new(&output_template)
+                    .with_extension(format.extension())
+                    .to_string_lossy()
+                    .to_string();
+                error!(
+                    "output path `{}` does not match format {:?}, writing `{}` instead",
+                    output_template, format, fixed
+                );
+                fixed
+            }
+            _ => output_template,
+        };
+        info!("output path: {}", output_template);
+        let output_path = PathTemplate::new(output_template).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
 
-        // 动态生成默认输出路径（如果未提供）
-        let output_path = args
-            .output_path
-            .map(Self::format_path)
-            .unwrap_or_else(Self::generate_default_output_path);
+        let (edit, pick_color, color_format) = match &args.command {
+            Some(Command::Edit { file }) => (Some(file.clone()), false, None),
+            Some(Command::PickColor { format }) => (None, true, format.clone()),
+            Some(Command::Measure) | None => (None, false, None),
+        };
+        let measure = matches!(args.command, Some(Command::Measure));
+        let color_format = color_format
+            .or(file.color_format.take())
+            .map(ColorFormat::new)
+            .transpose()
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            })
+            .unwrap_or_default();
 
         Cli {
-            no_cursor: !args.show_cursor,
+            no_cursor: !switch(args.show_cursor, args.hide_cursor)
+                .or(settings.show_cursor)
+                .unwrap_or(false),
             output_path,
-            quickshot: !args.no_quickshot,
-            auto_copy: !args.no_copy,
+            quickshot: switch(args.quickshot, args.no_quickshot)
+                .or(settings.quickshot)
+                .unwrap_or(true),
+            adjust: switch(args.adjust, args.no_adjust)
+                .or(settings.adjust)
+                .unwrap_or(false),
+            // NOTE: 取色时总是显示放大镜用于瞄准
+            loupe: pick_color
+                || switch(args.loupe, args.no_loupe)
+                    .or(settings.loupe)
+                    .unwrap_or(true),
+            snap: switch(args.snap, args.no_snap)
+                .or(settings.snap)
+                .unwrap_or(true),
+            auto_copy: switch(args.copy, args.no_copy)
+                .or(settings.copy)
+                .unwrap_or(true),
             full_screen: args.full_screen,
+            output: args.output.map(OutputTarget::from),
+            format,
+            quality: settings.quality.unwrap_or(90).clamp(1, 100),
+            project: switch(args.project, args.no_project)
+                .or(settings.project)
+                .unwrap_or(false),
+            edit,
+            pick_color,
+            color_format,
+            measure,
+            hooks: settings.hooks.unwrap_or_default(),
+            colors: file.colors,
+            keys: file.keys,
+            editor: file.editor,
         }
     }
 
-    fn format_path(path: PathBuf) -> PathBuf {
-        let path_str = path.to_string_lossy().to_string();
-        let formatted_path = Self::replace_time_specifiers(&path_str);
-        PathBuf::from(formatted_path)
+    /// `-o -` 时将图片写到 stdout
+    pub fn to_stdout(&self) -> bool {
+        self.output_path.is_stdout()
     }
 
-    fn replace_time_specifiers(path_str: &str) -> String {
-        let now = Local::now();
-        let mut formatted = path_str.to_string();
+    /// 由目录和文件名模板生成输出路径模板，目录默认为 xdg 图片目录
+    fn default_output_template(
+        dir: Option<String>,
+        filename: Option<String>,
+        format: ImageFormat,
+    ) -> String {
+        let dir = dir
+            .or_else(|| {
+                // NOTE: 目录中的 % 不是模板记号
+                UserDirs::new().and_then(|ud| {
+                    ud.picture_dir()
+                        .map(|p| p.to_string_lossy().replace('%', "%%"))
+                })
+            })
+            .unwrap_or_else(|| ".".to_string());
+        let filename = filename
+            .unwrap_or_else(|| format!("foam_shot-%Y-%m-%d-%H-%M-%S.{}", format.extension()));
+        format!("{}/{}", dir.trim_end_matches('/'), filename)
+    }
+}
 
-        formatted = formatted.replace("%Y", &now.format("%Y").to_string());
-        formatted = formatted.replace("%m", &now.format("%m").to_string());
-        formatted = formatted.replace("%d", &now.format("%d").to_string());
-        formatted = formatted.replace("%H", &now.format("%H").to_string());
-        formatted = formatted.replace("%M", &now.format("%M").to_string());
-        formatted = formatted.replace("%S", &now.format("%S").to_string());
+/// 图片格式，命令行优先于配置文件，同一层中明确指定的格式优先于路径的扩展名：
+/// --format、-o 的扩展名、配置的 format、配置的 output_path 或 filename 的扩展名，都没有时为 png
+fn resolve_format(
+    cli_format: Option<ImageFormat>,
+    cli_path: Option<&str>,
+    config_format: Option<ImageFormat>,
+    config_path: Option<&str>,
+) -> ImageFormat {
+    let from_path = |path: Option<&str>| path.and_then(|p| ImageFormat::from_path(Path::new(p)));
+    cli_format
+        .or(from_path(cli_path))
+        .or(config_format)
+        .or(from_path(config_path))
+        .unwrap_or_default()
+}
 
-        formatted
+/// 一对开关参数（如 --copy 和 --no-copy）的取值，都未指定时为 None，使用配置文件。
+/// NOTE: 两者互相 overrides_with，同时出现时只保留后出现的那个
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
     }
+}
 
-    fn generate_default_output_path() -> PathBuf {
-        let mut path = UserDirs::new()
-            .and_then(|ud| ud.picture_dir().map(|p| p.to_path_buf()))
-            .unwrap_or_else(|| PathBuf::from("."));
-
-        // 生成格式化的时间字符串
-        let now = Local::now();
-        let time_str = now.format("%Y-%m-%d-%H-%M-%S").to_string();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        path.push(format!("foam_shot-{}.png", time_str));
-        info!("output path: {}", path.display());
+    #[test]
+    fn format_precedence() {
+        use ImageFormat::*;
+        assert_eq!(resolve_format(None, None, None, None), Png);
+        assert_eq!(resolve_format(Some(Jpeg), Some("a.png"), None, None), Jpeg);
+        assert_eq!(resolve_format(None, Some("a.webp"), Some(Jpeg), None), Webp);
+        // NOTE: 配置中明确的 format 优先于配置路径的扩展名
+        assert_eq!(resolve_format(None, None, Some(Jpeg), Some("a.png")), Jpeg);
+        assert_eq!(resolve_format(None, None, None, Some("a.qoi")), Qoi);
+        assert_eq!(resolve_format(None, Some("shot"), None, Some("a.qoi")), Qoi);
+    }
 
-        path
+    #[test]
+    fn switches() {
+        assert_eq!(switch(false, false), None);
+        assert_eq!(switch(true, false), Some(true));
+        assert_eq!(switch(false, true), Some(false));
     }
 }
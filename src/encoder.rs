@@ -0,0 +1,199 @@
+use std::path::Path;
+
+use cairo::ImageSurface;
+use clap::ValueEnum;
+use jpeg_encoder::{ColorType, Encoder};
+use serde::Deserialize;
+
+/// 输出图片格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    #[serde(alias = "jpg")]
+    #[value(alias = "jpg")]
+    Jpeg,
+    /// 有损 WebP，使用 quality
+    Webp,
+    WebpLossless,
+    Qoi,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp | ImageFormat::WebpLossless => "webp",
+            ImageFormat::Qoi => "qoi",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp | ImageFormat::WebpLossless => "image/webp",
+            ImageFormat::Qoi => "image/qoi",
+        }
+    }
+
+    /// 由文件扩展名推断格式，.webp 视为有损 WebP
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            "webp" => Some(ImageFormat::Webp),
+            "qoi" => Some(ImageFormat::Qoi),
+            _ => None,
+        }
+    }
+
+    /// 将裁剪后的截图编码为该格式，quality 只用于有损格式
+    pub fn encode(&self, image: &ImageSurface, quality: u8) -> Result<Vec<u8>, String> {
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        match self {
+            ImageFormat::Png => {
+                let mut png = Vec::new();
+                image
+                    .write_to_png(&mut png)
+                    .map_err(|e| format!("failed to encode png: {}", e))?;
+                Ok(png)
+            }
+            ImageFormat::Jpeg => {
+                let (width, height) = jpeg_size(width, height)?;
+                let mut jpeg = Vec::new();
+                Encoder::new(&mut jpeg, quality)
+                    .encode(&rgb_pixels(image)?, width, height, ColorType::Rgb)
+                    .map_err(|e| format!("failed to encode jpeg: {}", e))?;
+                Ok(jpeg)
+            }
+            ImageFormat::Webp | ImageFormat::WebpLossless => {
+                webp::Encoder::from_rgb(&rgb_pixels(image)?, width, height)
+                    .encode_simple(*self == ImageFormat::WebpLossless, quality as f32)
+                    .map(|data| data.to_vec())
+                    .map_err(|e| format!("failed to encode webp: {:?}", e))
+            }
+            ImageFormat::Qoi => qoi::encode_to_vec(rgb_pixels(image)?, width, height)
+                .map_err(|e| format!("failed to encode qoi: {}", e)),
+        }
+    }
+}
+
+/// JPEG 的宽高最大为 u16::MAX
+fn jpeg_size(width: u32, height: u32) -> Result<(u16, u16), String> {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(w), Ok(h)) => Ok((w, h)),
+        _ => Err(format!("image {}x{} is too large for jpeg", width, height)),
+    }
+}
+
+/// 取出 surface 的像素，按 RGB 顺序紧密排列
+fn rgb_pixels(image: &ImageSurface) -> Result<Vec<u8>, String> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let stride = image.stride() as usize;
+    image.flush();
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    image
+        .with_data(|data| {
+            for row in data.chunks(stride).take(height) {
+                // NOTE: cairo Rgb24 在小端机器上为 BGRX
+                for pixel in row[..width * 4].chunks_exact(4) {
+                    rgb.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+                }
+            }
+        })
+        .map_err(|e| format!("failed to read image data: {}", e))?;
+    Ok(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 左半红色、右半蓝色的 4x2 图片
+    fn image() -> ImageSurface {
+        let mut surface = ImageSurface::create(cairo::Format::Rgb24, 4, 2).unwrap();
+        let stride = surface.stride() as usize;
+        let mut data = surface.data().unwrap();
+        for y in 0..2 {
+            for x in 0..4 {
+                // NOTE: cairo Rgb24 在小端机器上为 BGRX
+                let pixel = if x < 2 {
+                    [0, 0, 255, 0]
+                } else {
+                    [255, 0, 0, 0]
+                };
+                data[y * stride + x * 4..][..4].copy_from_slice(&pixel);
+            }
+        }
+        drop(data);
+        surface
+    }
+
+    const PIXELS: [u8; 24] = [
+        255, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255, //
+        255, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255,
+    ];
+
+    #[test]
+    fn rgb_order() {
+        assert_eq!(rgb_pixels(&image()).unwrap(), PIXELS);
+    }
+
+    #[test]
+    fn png() {
+        let png = ImageFormat::Png.encode(&image(), 90).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn jpeg() {
+        let jpeg = ImageFormat::Jpeg.encode(&image(), 90).unwrap();
+        assert!(jpeg.starts_with(&[0xff, 0xd8]));
+        assert_eq!(jpeg_size(65535, 1), Ok((65535, 1)));
+        assert!(jpeg_size(65536, 1).is_err());
+        assert!(jpeg_size(1, 65536).is_err());
+    }
+
+    #[test]
+    fn webp() {
+        for format in [ImageFormat::Webp, ImageFormat::WebpLossless] {
+            let webp = format.encode(&image(), 90).unwrap();
+            assert_eq!(&webp[..4], b"RIFF");
+            assert_eq!(&webp[8..12], b"WEBP");
+        }
+        let lossless = ImageFormat::WebpLossless.encode(&image(), 90).unwrap();
+        let decoded = webp::Decoder::new(&lossless).decode().unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (4, 2));
+        assert_eq!(&*decoded, &PIXELS);
+    }
+
+    #[test]
+    fn qoi() {
+        let qoi = ImageFormat::Qoi.encode(&image(), 90).unwrap();
+        let (header, pixels) = qoi::decode_to_vec(&qoi).unwrap();
+        assert_eq!((header.width, header.height), (4, 2));
+        assert_eq!(pixels, PIXELS);
+    }
+
+    #[test]
+    fn extension_roundtrip() {
+        for format in [
+            ImageFormat::Png,
+            ImageFormat::Jpeg,
+            ImageFormat::Webp,
+            ImageFormat::Qoi,
+        ] {
+            let path = format!("shot.{}", format.extension());
+            assert_eq!(ImageFormat::from_path(Path::new(&path)), Some(format));
+        }
+        assert_eq!(
+            ImageFormat::from_path(Path::new("a.JPEG")),
+            Some(ImageFormat::Jpeg)
+        );
+        assert_eq!(ImageFormat::from_path(Path::new("a.bmp")), None);
+    }
+}
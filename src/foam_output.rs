@@ -0,0 +1,259 @@
+use wayland_client::protocol::wl_output::{self, Transform};
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1;
+
+/// 一个已绑定的 wl_output 及其状态
+pub struct FoamOutput {
+    pub id: usize,
+    pub wl_output: wl_output::WlOutput,
+    pub xdg_output: Option<zxdg_output_v1::ZxdgOutputV1>,
+    /// 输出名称，如 DP-1、eDP-1
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// 当前模式的物理尺寸，即 screencopy buffer 的尺寸
+    pub width: i32,
+    pub height: i32,
+    pub scale: i32,
+    pub transform: Transform,
+    /// wp_fractional_scale_v1 给出的建议缩放比
+    pub preferred_scale: Option<f64>,
+    /// 在全局合成器坐标（逻辑坐标）中的位置
+    pub global_x: i32,
+    pub global_y: i32,
+    /// xdg-output 给出的逻辑尺寸
+    pub logical_width: Option<i32>,
+    pub logical_height: Option<i32>,
+}
+
+impl FoamOutput {
+    pub fn new(id: usize, wl_output: wl_output::WlOutput) -> Self {
+        Self {
+            id,
+            wl_output,
+            xdg_output: None,
+            name: None,
+            description: None,
+            width: 0,
+            height: 0,
+            scale: 1,
+            transform: Transform::Normal,
+            preferred_scale: None,
+            global_x: 0,
+            global_y: 0,
+            logical_width: None,
+            logical_height: None,
+        }
+    }
+
+    /// 名称或描述是否与给定的名称匹配
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.as_deref() == Some(name) || self.description.as_deref() == Some(name)
+    }
+
+    /// 应用 transform 后的物理尺寸，旋转 90/270 度时宽高互换
+    pub fn transformed_size(&self) -> (i32, i32) {
+        match self.transform {
+            Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+                (self.height, self.width)
+            }
+            _ => (self.width, self.height),
+        }
+    }
+
+    /// 逻辑尺寸，没有 xdg-output 时由模式尺寸、transform 和 scale 推算
+    pub fn logical_size(&self) -> (i32, i32) {
+        match (self.logical_width, self.logical_height) {
+            (Some(w), Some(h)) => (w, h),
+            _ => {
+                let (w, h) = self.transformed_size();
+                (w / self.scale, h / self.scale)
+            }
+        }
+    }
+
+    /// 输出在全局坐标中的矩形 (x, y, width, height)
+    pub fn global_rect(&self) -> (f64, f64, f64, f64) {
+        let (w, h) = self.logical_size();
+        (
+            self.global_x as f64,
+            self.global_y as f64,
+            w as f64,
+            h as f64,
+        )
+    }
+
+    /// 每个逻辑像素对应的 buffer 像素数
+    pub fn buffer_scale(&self) -> f64 {
+        let (w, _) = self.logical_size();
+        if w > 0 {
+            self.transformed_size().0 as f64 / w as f64
+        } else {
+            self.scale as f64
+        }
+    }
+
+    /// surface 局部坐标转换为全局坐标
+    pub fn to_global(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x + self.global_x as f64, y + self.global_y as f64)
+    }
+
+    /// 全局矩形与该输出的交集
+    pub fn intersect(&self, rect: (f64, f64, f64, f64)) -> Option<(f64, f64, f64, f64)> {
+        let (ox, oy, ow, oh) = self.global_rect();
+        let (x, y, w, h) = rect;
+        let left = x.max(ox);
+        let top = y.max(oy);
+        let right = (x + w).min(ox + ow);
+        let bottom = (y + h).min(oy + oh);
+        if right > left && bottom > top {
+            Some((left, top, right - left, bottom - top))
+        } else {
+            None
+        }
+    }
+
+    /// screencopy buffer 相对于逻辑画面的 transform
+    pub fn buffer_transform(&self, y_invert: bool) -> Transform {
+        buffer_transform(self.transform, y_invert)
+    }
+
+    /// 将 screencopy buffer 的像素坐标映射到逻辑方向的物理像素坐标
+    pub fn buffer_matrix(&self, y_invert: bool) -> cairo::Matrix {
+        buffer_matrix(self.transform, y_invert, (self.width, self.height))
+    }
+}
+
+/// buffer 是输出 transform 作用后的帧，y_invert 时还需再上下翻转（即 Flipped180）
+fn buffer_transform(transform: Transform, y_invert: bool) -> Transform {
+    if !y_invert {
+        return transform;
+    }
+    let m = multiply(
+        transform_matrix(Transform::Flipped180),
+        transform_matrix(transform),
+    );
+    TRANSFORMS
+        .into_iter()
+        .find(|&t| transform_matrix(t) == m)
+        .unwrap_or(Transform::Normal)
+}
+
+/// size 为 buffer 的尺寸
+fn buffer_matrix(transform: Transform, y_invert: bool, (w, h): (i32, i32)) -> cairo::Matrix {
+    // NOTE: 正交矩阵的逆即转置
+    let m = transform_matrix(buffer_transform(transform, y_invert));
+    let inv = [[m[0][0], m[1][0]], [m[0][1], m[1][1]]];
+    let corners = [(0, 0), (w, 0), (0, h), (w, h)]
+        .map(|(x, y)| (inv[0][0] * x + inv[0][1] * y, inv[1][0] * x + inv[1][1] * y));
+    let min_x = corners.iter().map(|c| c.0).min().unwrap_or(0);
+    let min_y = corners.iter().map(|c| c.1).min().unwrap_or(0);
+    cairo::Matrix::new(
+        inv[0][0] as f64,
+        inv[1][0] as f64,
+        inv[0][1] as f64,
+        inv[1][1] as f64,
+        -min_x as f64,
+        -min_y as f64,
+    )
+}
+
+/// transform 对应的矩阵（y 轴向下，作用于列向量），翻转的变体为先沿竖直轴翻转再逆时针旋转
+fn transform_matrix(transform: Transform) -> [[i32; 2]; 2] {
+    const ROTATE: [[[i32; 2]; 2]; 4] = [
+        [[1, 0], [0, 1]],
+        [[0, 1], [-1, 0]],
+        [[-1, 0], [0, -1]],
+        [[0, -1], [1, 0]],
+    ];
+    const FLIP: [[i32; 2]; 2] = [[-1, 0], [0, 1]];
+    match transform {
+        Transform::_90 => ROTATE[1],
+        Transform::_180 => ROTATE[2],
+        Transform::_270 => ROTATE[3],
+        Transform::Flipped => FLIP,
+        Transform::Flipped90 => multiply(ROTATE[1], FLIP),
+        Transform::Flipped180 => multiply(ROTATE[2], FLIP),
+        Transform::Flipped270 => multiply(ROTATE[3], FLIP),
+        _ => ROTATE[0],
+    }
+}
+
+fn multiply(a: [[i32; 2]; 2], b: [[i32; 2]; 2]) -> [[i32; 2]; 2] {
+    let mut m = [[0; 2]; 2];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = a[i][0] * b[0][j] + a[i][1] * b[1][j];
+        }
+    }
+    m
+}
+
+const TRANSFORMS: [Transform; 8] = [
+    Transform::Normal,
+    Transform::_90,
+    Transform::_180,
+    Transform::_270,
+    Transform::Flipped,
+    Transform::Flipped90,
+    Transform::Flipped180,
+    Transform::Flipped270,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x2 buffer 的左上、右上、左下角映射到的逻辑方向坐标
+    fn corners(transform: Transform, y_invert: bool) -> [(f64, f64); 3] {
+        let m = buffer_matrix(transform, y_invert, (4, 2));
+        [(0.0, 0.0), (4.0, 0.0), (0.0, 2.0)].map(|(x, y)| m.transform_point(x, y))
+    }
+
+    #[test]
+    fn buffer_corners() {
+        let cases = [
+            (Transform::Normal, [(0.0, 0.0), (4.0, 0.0), (0.0, 2.0)]),
+            (Transform::_90, [(2.0, 0.0), (2.0, 4.0), (0.0, 0.0)]),
+            (Transform::_180, [(4.0, 2.0), (0.0, 2.0), (4.0, 0.0)]),
+            (Transform::_270, [(0.0, 4.0), (0.0, 0.0), (2.0, 4.0)]),
+            (Transform::Flipped, [(4.0, 0.0), (0.0, 0.0), (4.0, 2.0)]),
+            (Transform::Flipped90, [(0.0, 0.0), (0.0, 4.0), (2.0, 0.0)]),
+            (Transform::Flipped180, [(0.0, 2.0), (4.0, 2.0), (0.0, 0.0)]),
+            (Transform::Flipped270, [(2.0, 4.0), (2.0, 0.0), (0.0, 4.0)]),
+        ];
+        for (transform, expected) in cases {
+            assert_eq!(corners(transform, false), expected, "{:?}", transform);
+        }
+    }
+
+    #[test]
+    fn buffer_corners_y_invert() {
+        let cases = [
+            (Transform::Normal, [(0.0, 2.0), (4.0, 2.0), (0.0, 0.0)]),
+            (Transform::_90, [(0.0, 0.0), (0.0, 4.0), (2.0, 0.0)]),
+            (Transform::_180, [(4.0, 0.0), (0.0, 0.0), (4.0, 2.0)]),
+            (Transform::_270, [(2.0, 4.0), (2.0, 0.0), (0.0, 4.0)]),
+            (Transform::Flipped, [(4.0, 2.0), (0.0, 2.0), (4.0, 0.0)]),
+            (Transform::Flipped90, [(2.0, 0.0), (2.0, 4.0), (0.0, 0.0)]),
+            (Transform::Flipped180, [(0.0, 0.0), (4.0, 0.0), (0.0, 2.0)]),
+            (Transform::Flipped270, [(0.0, 4.0), (0.0, 0.0), (2.0, 4.0)]),
+        ];
+        for (transform, expected) in cases {
+            assert_eq!(corners(transform, true), expected, "{:?}", transform);
+        }
+    }
+
+    #[test]
+    fn y_invert_transform() {
+        assert_eq!(
+            buffer_transform(Transform::Normal, true),
+            Transform::Flipped180
+        );
+        assert_eq!(buffer_transform(Transform::_90, true), Transform::Flipped90);
+        for transform in TRANSFORMS {
+            assert_eq!(buffer_transform(transform, false), transform);
+            // NOTE: 上下翻转两次还原
+            let inverted = buffer_transform(transform, true);
+            assert_eq!(buffer_transform(inverted, true), transform);
+        }
+    }
+}
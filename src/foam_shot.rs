@@ -1,10 +1,30 @@
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
 use log::*;
 use smithay_client_toolkit::shm::{Shm, slot::SlotPool};
-use wayland_client::{Connection, globals::registry_queue_init};
+use wayland_client::{
+    Connection, DispatchError, EventQueue, backend::WaylandError, globals::registry_queue_init,
+};
 use wayland_protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1;
+use xkbcommon::xkb;
+
+use crate::clipboard::Clipboard;
+use crate::config::OutputTarget;
+use crate::loupe::Loupe;
+use crate::mode::{
+    CopyHook, Mode, adjust_mode, edit_mode, freeze_mode, measure_mode, result_mode, select_mode,
+};
+use crate::project::Project;
+use crate::snap::{self, SNAP_DISTANCE};
+use crate::{config, mode, toplevels, wayland_ctx};
 
-use crate::mode::{CopyHook, Mode, freeze_mode, result_mode, select_mode};
-use crate::{config, mode, wayland_ctx};
+/// 打开的工程最多占输出尺寸的比例
+const PROJECT_FIT: f64 = 0.9;
+/// --full-screen --output focused 等待鼠标进入的最长时间，超时后截取第一个输出
+const FOCUS_TIMEOUT: Duration = Duration::from_millis(500);
 
 pub struct FoamShot {
     pub wayland_ctx: wayland_ctx::WaylandCtx,
@@ -12,8 +32,13 @@ pub struct FoamShot {
     pub cli: config::Cli,
     pub freeze_mode: freeze_mode::FreezeMode,
     pub select_mode: select_mode::SelectMode,
+    pub adjust_mode: adjust_mode::AdjustMode,
+    pub measure_mode: measure_mode::MeasureMode,
+    pub edit_mode: edit_mode::EditMode,
     pub result_mode: result_mode::ResultMode,
     pub mode: mode::Mode,
+    /// 后台查询窗口的线程，结束后由主循环取回结果
+    pub windows_query: Option<JoinHandle<Vec<toplevels::Rect>>>,
 }
 
 pub fn run_main_loop() {
@@ -30,19 +55,95 @@ pub fn run_main_loop() {
     let mut shot_foam = FoamShot::new(shm, pool, qh);
 
     event_queue.roundtrip(&mut shot_foam).expect("init failed");
+    // NOTE: 第二次 roundtrip 获取输出的模式、位置和 xdg-output 信息
+    shot_foam.wayland_ctx.request_xdg_outputs();
+    event_queue.roundtrip(&mut shot_foam).expect("init failed");
+
+    if shot_foam.wayland_ctx.foam_outputs.is_empty() {
+        error!("no output available");
+        std::process::exit(1);
+    }
+    if let Some(OutputTarget::Named(name)) = &shot_foam.cli.output {
+        match shot_foam.wayland_ctx.find_output(name) {
+            Some(id) => shot_foam.result_mode.target_output = Some(id),
+            None => {
+                let names: Vec<&str> = shot_foam
+                    .wayland_ctx
+                    .foam_outputs
+                    .iter()
+                    .filter_map(|o| o.name.as_deref())
+                    .collect();
+                error!("output {} not found, available: {}", name, names.join(", "));
+                std::process::exit(1);
+            }
+        }
+    }
 
-    if let None = shot_foam.wayland_ctx.screencopy_manager {
+    if let Some(path) = shot_foam.cli.edit.clone() {
+        // NOTE: 选择层收到 configure 后才能附加 buffer
+        shot_foam.select_mode.before(&mut shot_foam.wayland_ctx);
+        event_queue.roundtrip(&mut shot_foam).expect("init failed");
+        shot_foam.select_mode.on(&mut shot_foam.wayland_ctx);
+        shot_foam.open_project(&path);
+    } else if let None = shot_foam.wayland_ctx.screencopy_manager {
         error!("screencopy manager not available");
         std::process::exit(1);
     } else {
         info!("screencopy manager available");
     }
 
+    // NOTE: 截图时才需要窗口，窗口在冻结期间不会移动，只查询一次。
+    // 在后台线程中查询，不推迟冻结画面，也不阻塞事件循环
+    shot_foam.windows_query = (shot_foam.cli.edit.is_none()
+        && !shot_foam.cli.pick_color
+        && !shot_foam.cli.measure
+        && !shot_foam.cli.full_screen)
+        .then(|| {
+            let outputs: Vec<(String, (f64, f64))> = shot_foam
+                .wayland_ctx
+                .foam_outputs
+                .iter()
+                .filter_map(|o| {
+                    let name = o.name.clone()?;
+                    Some((name, (o.global_x as f64, o.global_y as f64)))
+                })
+                .collect();
+            std::thread::spawn(move || toplevels::query(&outputs))
+        });
+
     info!("into loop");
-    println!("{:?}", shot_foam.cli);
+    debug!("{:?}", shot_foam.cli);
+    // NOTE: 等待鼠标进入以确定聚焦输出的截止时间
+    let mut focus_deadline: Option<Instant> = None;
     loop {
-        std::thread::sleep(std::time::Duration::from_millis(16));
-        event_queue.blocking_dispatch(&mut shot_foam).unwrap();
+        std::thread::sleep(Duration::from_millis(16));
+        // NOTE: 按住可重复的按键或等待聚焦输出时最多等待到下一次重复或截止时间
+        let wake = match (shot_foam.wayland_ctx.key_repeat, focus_deadline) {
+            (Some((_, next)), Some(deadline)) => Some(next.min(deadline)),
+            (Some((_, next)), None) => Some(next),
+            (None, deadline) => deadline,
+        };
+        match wake {
+            Some(next) => dispatch_timeout(
+                &mut event_queue,
+                &mut shot_foam,
+                next.saturating_duration_since(Instant::now()),
+            ),
+            None => event_queue.blocking_dispatch(&mut shot_foam).map(|_| ()),
+        }
+        .unwrap();
+        shot_foam.repeat_key();
+        shot_foam.collect_windows(false);
+        if let Some(deadline) = focus_deadline
+            && (!matches!(shot_foam.mode, Mode::Await) || Instant::now() >= deadline)
+        {
+            focus_deadline = None;
+            if let Mode::Await = shot_foam.mode {
+                info!("pointer did not enter any output, capturing the first output");
+                shot_foam.result_mode.target_output = Some(0);
+                shot_foam.mode = Mode::Output;
+            }
+        }
         match &shot_foam.mode {
             Mode::Freeze(CopyHook::Request) => {
                 shot_foam.freeze_mode.before(&mut shot_foam.wayland_ctx);
@@ -53,19 +154,31 @@ pub fn run_main_loop() {
                 // NOTE: see ./imp/impl_foam_shot.rs for details
             }
             Mode::Freeze(CopyHook::Ready) => {
-                if shot_foam.cli.full_screen {
+                if shot_foam.cli.full_screen && shot_foam.cli.output == Some(OutputTarget::Focused)
+                {
+                    // NOTE: 需要鼠标进入冻结层才能知道所在的输出，见 ./imp/impl_foam_shot.rs
+                    shot_foam.result_mode.full_screen = true;
+                    shot_foam.freeze_mode.set_freeze(&mut shot_foam.wayland_ctx);
+                    shot_foam.mode = Mode::Await;
+                    focus_deadline = Some(Instant::now() + FOCUS_TIMEOUT);
+                } else if shot_foam.cli.full_screen {
                     shot_foam.result_mode.full_screen = true;
                     shot_foam.result_mode.to_png_2(
                         &mut shot_foam.cli,
                         &mut shot_foam.wayland_ctx,
                         &mut shot_foam.freeze_mode,
+                        &[],
                     );
-                    shot_foam.mode = Mode::Exit;
+                    shot_foam.mode = shot_foam.after_output(&connection);
                 } else {
                     shot_foam.freeze_mode.set_freeze(&mut shot_foam.wayland_ctx);
                     shot_foam.mode = Mode::PreSelect;
                 }
             }
+            // NOTE: 编辑工程时没有冻结画面，返回框选即退出
+            Mode::PreSelect if shot_foam.result_mode.source.is_some() => {
+                shot_foam.mode = Mode::Exit;
+            }
             Mode::PreSelect => {
                 shot_foam.select_mode.on(&mut shot_foam.wayland_ctx);
                 shot_foam
@@ -74,26 +187,91 @@ pub fn run_main_loop() {
                 shot_foam.mode = Mode::Await;
             }
             Mode::Await => {
-                // NOTE: 这个模式不做处理，用于等待鼠标按下
+                // NOTE: 等待鼠标按下，只在鼠标移动时重绘悬停的窗口和放大镜
+                if shot_foam.result_mode.full_screen || shot_foam.select_mode.surfaces.is_empty() {
+                    continue;
+                }
+                let Some(pos) = shot_foam.wayland_ctx.current_pos else {
+                    continue;
+                };
+                if pos == shot_foam.select_mode.last_pos {
+                    continue;
+                }
+                shot_foam.select_mode.last_pos = pos;
+                let hover = shot_foam.select_mode.window_at(pos);
+                let loupe = shot_foam.loupe();
+                if hover == shot_foam.select_mode.hover && loupe.is_none() {
+                    continue;
+                }
+                shot_foam
+                    .select_mode
+                    .draw_await(&mut shot_foam.wayland_ctx, hover, loupe.as_ref());
+                event_queue.roundtrip(&mut shot_foam).unwrap();
             }
             Mode::OnDraw => {
-                if let Some((end_x, end_y)) = shot_foam.wayland_ctx.current_pos {
-                    if (end_x, end_y) == shot_foam.select_mode.last_pos {
-                        // NOTE: 鼠标没有移动
-                        continue;
-                    }
+                let Some(pos) = shot_foam.wayland_ctx.current_pos else {
+                    continue;
+                };
+                // NOTE: 鼠标没有移动且没有切换吸附时不重绘
+                if pos == shot_foam.select_mode.last_pos && !shot_foam.select_mode.dirty {
+                    continue;
+                }
+                shot_foam.select_mode.dirty = false;
+                let end = shot_foam.snap_point(pos);
+                let loupe = shot_foam.loupe();
+                shot_foam
+                    .select_mode
+                    .after(&mut shot_foam.wayland_ctx, end, loupe.as_ref());
+                event_queue.roundtrip(&mut shot_foam).unwrap();
+            }
+            Mode::Adjust => {
+                if !shot_foam.adjust_mode.dirty {
+                    continue;
                 }
-                shot_foam.select_mode.after(&mut shot_foam.wayland_ctx);
+                shot_foam.adjust_mode.dirty = false;
+                shot_foam
+                    .select_mode
+                    .draw_adjust(&mut shot_foam.wayland_ctx, &shot_foam.adjust_mode);
                 event_queue.roundtrip(&mut shot_foam).unwrap();
             }
-            Mode::ShowResult => {}
+            Mode::Measure => {
+                if !shot_foam.measure_mode.dirty {
+                    continue;
+                }
+                shot_foam.measure_mode.dirty = false;
+                let loupe = shot_foam.loupe();
+                shot_foam.select_mode.draw_measure(
+                    &mut shot_foam.wayland_ctx,
+                    &shot_foam.measure_mode,
+                    loupe.as_ref(),
+                );
+                event_queue.roundtrip(&mut shot_foam).unwrap();
+            }
+            Mode::ShowResult => {
+                if !shot_foam.edit_mode.dirty {
+                    continue;
+                }
+                shot_foam.edit_mode.dirty = false;
+                shot_foam
+                    .select_mode
+                    .draw_edit(&mut shot_foam.wayland_ctx, &shot_foam.edit_mode);
+                event_queue.roundtrip(&mut shot_foam).unwrap();
+            }
+            Mode::Output if shot_foam.cli.pick_color => {
+                shot_foam.pick_color();
+                shot_foam.mode = shot_foam.after_output(&connection);
+            }
             Mode::Output => {
                 shot_foam.result_mode.to_png_2(
                     &mut shot_foam.cli,
                     &mut shot_foam.wayland_ctx,
                     &mut shot_foam.freeze_mode,
+                    &shot_foam.edit_mode.annotations,
                 );
-                shot_foam.mode = Mode::Exit;
+                shot_foam.mode = shot_foam.after_output(&connection);
+            }
+            Mode::ServeClipboard => {
+                // NOTE: 等待剪贴板请求，见 ./imp/impl_foam_shot.rs
             }
             Mode::Exit => {
                 std::process::exit(0);
@@ -109,10 +287,229 @@ impl FoamShot {
         Self {
             wayland_ctx: wayland_ctx::WaylandCtx::new(shm, pool, qh),
             freeze_mode: mode::freeze_mode::FreezeMode::new(cli.no_cursor),
-            select_mode: mode::select_mode::SelectMode::default(),
+            select_mode: mode::select_mode::SelectMode::new(cli.colors.clone()),
+            adjust_mode: mode::adjust_mode::AdjustMode::default(),
+            measure_mode: mode::measure_mode::MeasureMode::default(),
+            edit_mode: mode::edit_mode::EditMode::new(cli.editor.clone()),
             result_mode: mode::result_mode::ResultMode::new(cli.quickshot),
             cli,
             mode: mode::Mode::default(),
+            windows_query: None,
+        }
+    }
+
+    /// 到达重复时间时再次处理按住的按键
+    fn repeat_key(&mut self) {
+        let Some((key, next)) = self.wayland_ctx.key_repeat else {
+            return;
+        };
+        let now = Instant::now();
+        if now < next {
+            return;
+        }
+        // NOTE: 按住期间合成器可能通过 RepeatInfo 关闭重复，模式也可能已经改变
+        let (rate, _) = self.wayland_ctx.repeat_info;
+        let keysym = self
+            .wayland_ctx
+            .xkb_state
+            .as_ref()
+            .map(|s| s.key_get_one_sym(xkb::Keycode::new(key + 8)));
+        if rate <= 0 || !keysym.is_some_and(|keysym| self.key_repeats(keysym)) {
+            self.wayland_ctx.key_repeat = None;
+            return;
+        }
+        self.wayland_ctx.key_repeat = Some((key, now + Duration::from_secs(1) / rate as u32));
+        self.key_pressed(key);
+    }
+
+    /// 光标处的放大镜，未启用时为 None
+    fn loupe(&mut self) -> Option<Loupe> {
+        if !self.cli.loupe {
+            return None;
+        }
+        let pos = self.wayland_ctx.current_pos?;
+        Loupe::capture(
+            &self.result_mode,
+            &mut self.wayland_ctx,
+            &mut self.freeze_mode,
+            pos,
+        )
+    }
+
+    /// 框选时将 pos 吸附到附近的窗口边缘和画面边缘；按住 Ctrl 时临时切换是否吸附
+    pub fn snap_point(&mut self, pos: (f64, f64)) -> (f64, f64) {
+        if self.cli.snap == self.wayland_ctx.modifier_active(xkb::MOD_NAME_CTRL) {
+            return pos;
+        }
+        let size = 2.0 * SNAP_DISTANCE;
+        let region = (pos.0 - SNAP_DISTANCE, pos.1 - SNAP_DISTANCE, size, size);
+        let patch =
+            self.result_mode
+                .composite(&mut self.wayland_ctx, &mut self.freeze_mode, region);
+        snap::snap(pos, &self.select_mode.windows, patch.as_ref())
+    }
+
+    /// 取光标处的颜色，输出到 stdout 并复制到剪贴板
+    fn pick_color(&mut self) {
+        let Some(pos) = self.wayland_ctx.current_pos else {
+            return;
+        };
+        let Some(rgb) = self.freeze_mode.pixel(&mut self.wayland_ctx, pos) else {
+            return;
+        };
+        let color = self.cli.color_format.format(rgb);
+        info!("picked color {} at {:?}", color, pos);
+        println!("{}", color);
+        let clipboard = Clipboard::Text(color);
+        if self.cli.auto_copy && clipboard.offer(&self.wayland_ctx) {
+            self.result_mode.clipboard = Some(clipboard);
+        }
+    }
+
+    /// 确定选区后直接截图，或关闭 quickshot 时进入编辑
+    pub fn confirm_selection(&mut self, region: (f64, f64, f64, f64)) {
+        let (x, y, w, h) = region;
+        self.wayland_ctx.start_pos = Some((x, y));
+        self.wayland_ctx.end_pos = Some((x + w, y + h));
+        if self.cli.quickshot {
+            self.mode = Mode::Output;
+            return;
         }
+        let base = self
+            .result_mode
+            .composite(&mut self.wayland_ctx, &mut self.freeze_mode, region);
+        self.edit_mode.begin(region, &self.wayland_ctx, base);
+        self.mode = Mode::ShowResult;
+    }
+
+    /// 打开工程文件，截图居中显示在第一个输出的选择层上，过大时缩小，直接进入编辑
+    fn open_project(&mut self, path: &Path) {
+        let (project, image) = Project::load(path).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        info!("project: {}", path.display());
+
+        let (ox, oy, ow, oh) = self.wayland_ctx.foam_outputs[0].global_rect();
+        let fit = (ow * PROJECT_FIT / project.width)
+            .min(oh * PROJECT_FIT / project.height)
+            .min(1.0);
+        let (width, height) = (project.width * fit, project.height * fit);
+        let region = (
+            ox + (ow - width) / 2.0,
+            oy + (oh - height) / 2.0,
+            width,
+            height,
+        );
+
+        self.edit_mode
+            .begin(region, &self.wayland_ctx, Some(image.clone()));
+        self.edit_mode.annotations = project
+            .annotations
+            .into_iter()
+            .map(|mut annotation| {
+                annotation.zoom((0.0, 0.0), fit);
+                annotation.translate((region.0, region.1));
+                annotation
+            })
+            .collect();
+        self.result_mode.source = Some(result_mode::Source { image, region, fit });
+        self.mode = Mode::ShowResult;
+    }
+
+    /// 取回后台查询到的窗口。wait 为 false 时查询未结束则留到下次，为 true 时等待线程结束
+    fn collect_windows(&mut self, wait: bool) {
+        if !self
+            .windows_query
+            .as_ref()
+            .is_some_and(|query| wait || query.is_finished())
+        {
+            return;
+        }
+        let Some(query) = self.windows_query.take() else {
+            return;
+        };
+        let windows = query.join().unwrap_or_default();
+        let Some((bx, by, bw, bh)) = self.wayland_ctx.layout_bounds() else {
+            return;
+        };
+        // NOTE: 超出屏幕的部分无法截取，裁剪到所有输出的范围内
+        self.select_mode.windows = windows
+            .into_iter()
+            .filter_map(|(x, y, w, h)| {
+                let (left, top) = (x.max(bx), y.max(by));
+                let (right, bottom) = ((x + w).min(bx + bw), (y + h).min(by + bh));
+                (right > left && bottom > top).then_some((left, top, right - left, bottom - top))
+            })
+            .collect();
+    }
+
+    /// 输出完成后关闭界面；如果设置了剪贴板，fork 出子进程继续提供剪贴板内容，父进程直接退出
+    fn after_output(&mut self, connection: &Connection) -> Mode {
+        if self.result_mode.clipboard.is_none() {
+            return Mode::Exit;
+        }
+        self.select_mode.destroy();
+        self.freeze_mode.destroy();
+        if let Err(e) = connection.flush() {
+            error!("failed to flush wayland connection: {}", e);
+            return Mode::Exit;
+        }
+        // NOTE: fork 只复制当前线程，先等待查询窗口的线程结束
+        self.collect_windows(true);
+        // SAFETY: 此时只剩主线程，子进程只继续使用已有的 wayland 连接
+        match unsafe { libc::fork() } {
+            -1 => {
+                error!("failed to fork clipboard process");
+                Mode::Exit
+            }
+            0 => {
+                // NOTE: 子进程不能继续持有 stdout，否则管道另一端等不到 EOF
+                release_stdout();
+                info!("serving clipboard in background");
+                Mode::ServeClipboard
+            }
+            _ => std::process::exit(0),
+        }
+    }
+}
+
+/// 与 blocking_dispatch 相同，但最多等待 timeout
+fn dispatch_timeout(
+    event_queue: &mut EventQueue<FoamShot>,
+    state: &mut FoamShot,
+    timeout: Duration,
+) -> Result<(), DispatchError> {
+    event_queue.dispatch_pending(state)?;
+    event_queue.flush()?;
+    if let Some(guard) = event_queue.prepare_read() {
+        let mut fds = [libc::pollfd {
+            fd: guard.connection_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        // SAFETY: fds 在调用期间有效
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), 1, timeout.as_millis() as i32) };
+        // NOTE: 超时时丢弃 guard 即取消读取
+        if ready > 0 {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    event_queue.dispatch_pending(state)?;
+    Ok(())
+}
+
+/// 将 stdout 重定向到 /dev/null
+fn release_stdout() {
+    match std::fs::File::open("/dev/null") {
+        // SAFETY: dup2 只替换 fd 1，不影响其他 fd
+        Ok(null) => unsafe {
+            libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO);
+        },
+        Err(e) => error!("failed to open /dev/null: {}", e),
     }
 }
@@ -1,13 +1,30 @@
-use log::debug;
+use std::time::{Duration, Instant};
+
+use log::{debug, error};
 use smithay_client_toolkit::{delegate_shm, shm::ShmHandler};
 use wayland_client::{
-    Dispatch, Proxy,
-    protocol::{wl_compositor, wl_keyboard, wl_output, wl_pointer, wl_registry, wl_seat},
+    Dispatch, Proxy, event_created_child,
+    protocol::{
+        wl_compositor, wl_data_device, wl_data_device_manager, wl_data_offer, wl_data_source,
+        wl_keyboard, wl_output, wl_pointer, wl_registry, wl_seat,
+    },
 };
 use wayland_protocols::{
-    wp::cursor_shape::v1::client::wp_cursor_shape_manager_v1, xdg::shell::client::xdg_wm_base,
+    wp::{
+        cursor_shape::v1::client::wp_cursor_shape_manager_v1,
+        fractional_scale::v1::client::{wp_fractional_scale_manager_v1, wp_fractional_scale_v1},
+        viewporter::client::wp_viewporter,
+    },
+    xdg::{
+        shell::client::xdg_wm_base,
+        xdg_output::zv1::client::{zxdg_output_manager_v1, zxdg_output_v1},
+    },
 };
 use wayland_protocols_wlr::{
+    data_control::v1::client::{
+        zwlr_data_control_device_v1, zwlr_data_control_manager_v1, zwlr_data_control_offer_v1,
+        zwlr_data_control_source_v1,
+    },
     layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1},
     screencopy::v1::client::{
         zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
@@ -15,6 +32,9 @@ use wayland_protocols_wlr::{
     },
 };
 
+use xkbcommon::xkb;
+
+use crate::foam_output::FoamOutput;
 use crate::mode::{self, Mode};
 use crate::{foam_shot::FoamShot, mode::CopyHook};
 impl Dispatch<wl_registry::WlRegistry, ()> for FoamShot {
@@ -43,11 +63,15 @@ impl Dispatch<wl_registry::WlRegistry, ()> for FoamShot {
                     state.wayland_ctx.pointer = Some(seat.get_pointer(qh, ()));
                     state.wayland_ctx.keyboard = Some(seat.get_keyboard(qh, ()));
                     state.wayland_ctx.seat = Some(seat);
-                } else if interface == wl_output::WlOutput::interface().name
-                    && state.wayland_ctx.output.is_none()
-                {
-                    let output = proxy.bind(name, version, qh, ());
-                    state.wayland_ctx.output = Some(output);
+                } else if interface == wl_output::WlOutput::interface().name {
+                    // NOTE: 绑定所有输出，输出 id 即其在 foam_outputs 中的下标
+                    let id = state.wayland_ctx.foam_outputs.len();
+                    // NOTE: v4 才有 name 和 description 事件
+                    let output = proxy.bind(name, version.min(4), qh, id);
+                    state
+                        .wayland_ctx
+                        .foam_outputs
+                        .push(FoamOutput::new(id, output));
                 } else if interface == zwlr_layer_shell_v1::ZwlrLayerShellV1::interface().name
                     && state.wayland_ctx.layer_shell.is_none()
                 {
@@ -76,6 +100,32 @@ impl Dispatch<wl_registry::WlRegistry, ()> for FoamShot {
                     && state.wayland_ctx.xdg_shell.is_none()
                 {
                     state.wayland_ctx.xdg_shell = Some(proxy.bind(name, version, qh, ()));
+                } else if interface == zxdg_output_manager_v1::ZxdgOutputManagerV1::interface().name
+                    && state.wayland_ctx.xdg_output_manager.is_none()
+                {
+                    state.wayland_ctx.xdg_output_manager =
+                        Some(proxy.bind(name, version.min(3), qh, ()));
+                } else if interface == wl_data_device_manager::WlDataDeviceManager::interface().name
+                    && state.wayland_ctx.data_device_manager.is_none()
+                {
+                    state.wayland_ctx.data_device_manager =
+                        Some(proxy.bind(name, version.min(3), qh, ()));
+                } else if interface
+                    == zwlr_data_control_manager_v1::ZwlrDataControlManagerV1::interface().name
+                    && state.wayland_ctx.data_control_manager.is_none()
+                {
+                    state.wayland_ctx.data_control_manager =
+                        Some(proxy.bind(name, version.min(2), qh, ()));
+                } else if interface == wp_viewporter::WpViewporter::interface().name
+                    && state.wayland_ctx.viewporter.is_none()
+                {
+                    state.wayland_ctx.viewporter = Some(proxy.bind(name, version, qh, ()));
+                } else if interface
+                    == wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1::interface().name
+                    && state.wayland_ctx.fractional_scale_manager.is_none()
+                {
+                    state.wayland_ctx.fractional_scale_manager =
+                        Some(proxy.bind(name, version, qh, ()));
                 }
             }
             wl_registry::Event::GlobalRemove { .. } => {
@@ -95,49 +145,71 @@ impl Dispatch<wl_registry::WlRegistry, ()> for FoamShot {
 }
 
 #[allow(unused_variables)]
-impl Dispatch<wl_output::WlOutput, ()> for FoamShot {
+impl Dispatch<wl_output::WlOutput, usize> for FoamShot {
     fn event(
         state: &mut Self,
         proxy: &wl_output::WlOutput,
         event: <wl_output::WlOutput as Proxy>::Event,
-        data: &(),
+        data: &usize,
         conn: &wayland_client::Connection,
         qh: &wayland_client::QueueHandle<Self>,
     ) {
         match event {
             // 处理输出设备的模式事件
             wl_output::Event::Mode {
-                flags: _,
+                flags,
                 width,
                 height,
                 refresh: _,
             } => {
-                state.wayland_ctx.height = Some(height);
-                state.wayland_ctx.width = Some(width);
+                // 只记录当前模式
+                if let wayland_client::WEnum::Value(flags) = flags
+                    && !flags.contains(wl_output::Mode::Current)
+                {
+                    return;
+                }
+                let output = &mut state.wayland_ctx.foam_outputs[*data];
+                output.width = width;
+                output.height = height;
             }
             // 处理输出设备的几何事件
             wl_output::Event::Geometry {
-                x: _,
-                y: _,
+                x,
+                y,
                 physical_width: _,
                 physical_height: _,
                 subpixel: _,
                 make: _,
                 model: _,
-                transform: _,
+                transform,
             } => {
-                // 为此输出设备创建一个表面并存储它
-                if let Some(compositor) = &state.wayland_ctx.compositor {
-                    // TODO:
-                    // state.freeze_mode.surface = Some(compositor.create_surface(qh, 1));
-                    // state.select_mode.surface = Some(compositor.create_surface(qh, 2));
+                // NOTE: 有 xdg-output 时以其逻辑位置为准
+                let output = &mut state.wayland_ctx.foam_outputs[*data];
+                if let wayland_client::WEnum::Value(transform) = transform {
+                    output.transform = transform;
                 }
+                if output.xdg_output.is_none() {
+                    output.global_x = x;
+                    output.global_y = y;
+                }
+            }
+            wl_output::Event::Scale { factor } => {
+                state.wayland_ctx.foam_outputs[*data].scale = factor;
+            }
+            wl_output::Event::Name { name } => {
+                state.wayland_ctx.foam_outputs[*data].name = Some(name);
+            }
+            wl_output::Event::Description { description } => {
+                state.wayland_ctx.foam_outputs[*data].description = Some(description);
             }
             _ => {}
         };
     }
 }
 
+/// linux/input-event-codes.h 中的鼠标右键
+const BTN_RIGHT: u32 = 0x111;
+
 #[allow(unused_variables)]
 impl Dispatch<wl_pointer::WlPointer, ()> for FoamShot {
     fn event(
@@ -149,9 +221,27 @@ impl Dispatch<wl_pointer::WlPointer, ()> for FoamShot {
         qh: &wayland_client::QueueHandle<Self>,
     ) {
         match event {
-            wl_pointer::Event::Enter { surface, .. } => {
-                if surface == *state.select_mode.surface.as_ref().unwrap() {
-                    debug!("鼠标进入表面2");
+            wl_pointer::Event::Enter {
+                surface,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                let id = state
+                    .select_mode
+                    .output_of(&surface)
+                    .or_else(|| state.freeze_mode.output_of(&surface));
+                if let Some(id) = id {
+                    debug!("鼠标进入输出 {}", id);
+                    state.wayland_ctx.pointer_output = Some(id);
+                    state.wayland_ctx.current_pos =
+                        Some(state.wayland_ctx.foam_outputs[id].to_global((surface_x, surface_y)));
+                }
+                // NOTE: --full-screen --output focused 等待鼠标进入后截取所在输出
+                if let Mode::Await = state.mode
+                    && state.cli.full_screen
+                {
+                    state.mode = Mode::Output;
                 }
             }
             wl_pointer::Event::Button {
@@ -160,24 +250,75 @@ impl Dispatch<wl_pointer::WlPointer, ()> for FoamShot {
                 button,
                 state: button_state,
             } => {
+                state.wayland_ctx.serial = Some(serial);
                 if let Some((x, y)) = state.wayland_ctx.current_pos {
                     if button_state
                         == wayland_client::WEnum::Value(wl_pointer::ButtonState::Pressed)
                     {
-                        if let Mode::Await = state.mode {
-                            state.wayland_ctx.start_pos = Some((x, y));
-                            state.mode = Mode::OnDraw;
+                        match state.mode {
+                            Mode::Await if state.cli.pick_color => {
+                                state.mode = Mode::Output;
+                            }
+                            Mode::Await | Mode::Measure if state.cli.measure => {
+                                let rect = state.wayland_ctx.modifier_active(xkb::MOD_NAME_ALT);
+                                let scale = state.wayland_ctx.pointer_output.map_or(1.0, |id| {
+                                    state.wayland_ctx.foam_outputs[id].buffer_scale()
+                                });
+                                state.measure_mode.press((x, y), rect, scale);
+                                state.mode = Mode::Measure;
+                            }
+                            Mode::Await => {
+                                state.wayland_ctx.start_pos = Some((x, y));
+                                state.select_mode.start = state.snap_point((x, y));
+                                state.mode = Mode::OnDraw;
+                            }
+                            Mode::Adjust => {
+                                // NOTE: 按在选区外时重新框选
+                                let grabbed = state.adjust_mode.press((x, y));
+                                if !grabbed {
+                                    state.wayland_ctx.start_pos = Some((x, y));
+                                    state.select_mode.start = state.snap_point((x, y));
+                                    state.mode = Mode::OnDraw;
+                                }
+                            }
+                            Mode::ShowResult if button == BTN_RIGHT => {
+                                state.edit_mode.remove_at((x, y));
+                            }
+                            Mode::ShowResult => {
+                                if let Some(mode) = state.edit_mode.press((x, y)) {
+                                    state.mode = mode;
+                                }
+                            }
+                            _ => {}
                         }
                     } else if button_state
                         == wayland_client::WEnum::Value(wl_pointer::ButtonState::Released)
                     {
-                        if let Mode::OnDraw = state.mode {
-                            state.wayland_ctx.end_pos = Some((x, y));
-                            if state.cli.quickshot {
-                                state.mode = Mode::Output
-                            } else {
-                                state.mode = Mode::ShowResult;
+                        match state.mode {
+                            Mode::OnDraw => {
+                                // NOTE: 没有拖动的单击选择鼠标下的窗口，否则使用吸附后的选区
+                                let clicked = state.wayland_ctx.start_pos.and_then(|start| {
+                                    state.select_mode.clicked_window(start, (x, y))
+                                });
+                                state.wayland_ctx.start_pos = Some(state.select_mode.start);
+                                state.wayland_ctx.end_pos = Some(state.snap_point((x, y)));
+                                let region = clicked.or_else(|| {
+                                    state.result_mode.calculate_region(&state.wayland_ctx)
+                                });
+                                let bounds = state.wayland_ctx.layout_bounds();
+                                if let (Some(region), Some(bounds)) = (region, bounds) {
+                                    if state.cli.adjust {
+                                        state.adjust_mode.begin(region, bounds);
+                                        state.mode = Mode::Adjust;
+                                    } else {
+                                        state.confirm_selection(region);
+                                    }
+                                }
                             }
+                            Mode::Adjust => state.adjust_mode.release(),
+                            Mode::Measure => state.measure_mode.release(),
+                            Mode::ShowResult => state.edit_mode.release(),
+                            _ => {}
                         }
                     }
                 } else {
@@ -190,9 +331,34 @@ impl Dispatch<wl_pointer::WlPointer, ()> for FoamShot {
                 surface_y,
                 time,
             } => {
-                state.wayland_ctx.current_pos = Some((surface_x.max(0.0), surface_y.max(0.0)));
+                // NOTE: 拖动时坐标始终相对于按下时所在的 surface，可能超出该输出
+                let Some(id) = state.wayland_ctx.pointer_output else {
+                    return;
+                };
+                let pos = state.wayland_ctx.foam_outputs[id].to_global((surface_x, surface_y));
+                state.wayland_ctx.current_pos = Some(pos);
+                match state.mode {
+                    Mode::ShowResult => state.edit_mode.motion(pos),
+                    Mode::Measure => state.measure_mode.motion(pos),
+                    Mode::Adjust => {
+                        state.adjust_mode.motion(pos);
+                        let shape = state.adjust_mode.cursor_at(pos);
+                        if state.adjust_mode.cursor != Some(shape) {
+                            state.adjust_mode.cursor = Some(shape);
+                            state.wayland_ctx.set_cursor_shape(shape);
+                        }
+                    }
+                    _ => {}
+                }
                 if state.wayland_ctx.start_pos.is_none() {
-                    state.wayland_ctx.start_pos = Some((surface_x.max(0.0), surface_y.max(0.0)));
+                    state.wayland_ctx.start_pos = Some(pos);
+                }
+            }
+            wl_pointer::Event::Axis { axis, value, .. } => {
+                if let Mode::ShowResult = state.mode
+                    && axis == wayland_client::WEnum::Value(wl_pointer::Axis::VerticalScroll)
+                {
+                    state.edit_mode.axis(value);
                 }
             }
             _ => {}
@@ -210,45 +376,221 @@ impl Dispatch<wl_keyboard::WlKeyboard, ()> for FoamShot {
         conn: &wayland_client::Connection,
         qh: &wayland_client::QueueHandle<Self>,
     ) {
-        if let wl_keyboard::Event::Key {
-            serial: _,
-            time: _,
-            key,
-            state: key_state,
-        } = event
-        {
-            if let wayland_client::WEnum::Value(wl_keyboard::KeyState::Pressed) = key_state {
-                println!("{}", key);
-                match state.mode {
-                    Mode::ShowResult => {
-                        if key == 1 {
-                            state.mode = Mode::PreSelect;
-                        }
+        match event {
+            wl_keyboard::Event::Keymap { format, fd, size } => {
+                if format != wayland_client::WEnum::Value(wl_keyboard::KeymapFormat::XkbV1) {
+                    return;
+                }
+                let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+                // SAFETY: fd 和 size 由合成器提供
+                let keymap = unsafe {
+                    xkb::Keymap::new_from_fd(
+                        &context,
+                        fd,
+                        size as usize,
+                        xkb::KEYMAP_FORMAT_TEXT_V1,
+                        xkb::KEYMAP_COMPILE_NO_FLAGS,
+                    )
+                };
+                match keymap {
+                    Ok(Some(keymap)) => {
+                        state.wayland_ctx.xkb_state = Some(xkb::State::new(&keymap));
                     }
-                    Mode::Await => {
-                        if key == 30 {
-                            println!("full screen");
-                            state.result_mode.full_screen = true;
-                            state.mode = Mode::Output;
-                        }
-                        if key == 1 {
-                            state.mode = Mode::Exit;
+                    _ => error!("failed to compile keymap"),
+                }
+                // NOTE: 按 LC_ALL、LC_CTYPE、LANG 的顺序确定 locale
+                let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .into_iter()
+                    .filter_map(std::env::var_os)
+                    .find(|v| !v.is_empty())
+                    .unwrap_or_else(|| "C".into());
+                match xkb::compose::Table::new_from_locale(
+                    &context,
+                    &locale,
+                    xkb::compose::COMPILE_NO_FLAGS,
+                ) {
+                    Ok(table) => {
+                        state.wayland_ctx.compose_state = Some(xkb::compose::State::new(
+                            &table,
+                            xkb::compose::STATE_NO_FLAGS,
+                        ));
+                    }
+                    Err(_) => debug!("no compose table for locale {:?}", locale),
+                }
+            }
+            wl_keyboard::Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                if let Some(xkb_state) = state.wayland_ctx.xkb_state.as_mut() {
+                    xkb_state.update_mask(mods_depressed, mods_latched, mods_locked, 0, 0, group);
+                }
+                // NOTE: 按下或松开 Ctrl 会切换吸附，需要重绘选区
+                if let Mode::OnDraw = state.mode {
+                    state.select_mode.dirty = true;
+                }
+            }
+            wl_keyboard::Event::Enter { serial, .. } => {
+                state.wayland_ctx.serial = Some(serial);
+            }
+            wl_keyboard::Event::Key {
+                serial,
+                time: _,
+                key,
+                state: key_state,
+            } => {
+                state.wayland_ctx.serial = Some(serial);
+                match key_state {
+                    wayland_client::WEnum::Value(wl_keyboard::KeyState::Pressed) => {
+                        state.key_pressed(key);
+                        // NOTE: xkb 键码为 evdev 键码加 8；按键处理后再判断，此时已是按键切换到的模式
+                        let keycode = xkb::Keycode::new(key + 8);
+                        let repeats = state.wayland_ctx.xkb_state.as_ref().is_some_and(|s| {
+                            s.get_keymap().key_repeats(keycode)
+                                && state.key_repeats(s.key_get_one_sym(keycode))
+                        });
+                        let (rate, delay) = state.wayland_ctx.repeat_info;
+                        state.wayland_ctx.key_repeat = (repeats && rate > 0)
+                            .then(|| (key, Instant::now() + Duration::from_millis(delay as u64)));
+                    }
+                    _ => {
+                        if state.wayland_ctx.key_repeat.is_some_and(|(k, _)| k == key) {
+                            state.wayland_ctx.key_repeat = None;
                         }
                     }
-                    _ => {}
                 }
             }
+            wl_keyboard::Event::RepeatInfo { rate, delay } => {
+                debug!("key repeat rate {} delay {}", rate, delay);
+                state.wayland_ctx.repeat_info = (rate, delay);
+            }
+            wl_keyboard::Event::Leave { .. } => {
+                state.wayland_ctx.key_repeat = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl FoamShot {
+    /// 按住 keysym 时是否重复。只重复调整选区的方向键和输入的文字，
+    /// Return、Escape 等会切换模式的按键不重复，避免按住时连续确认或退出
+    pub fn key_repeats(&self, keysym: xkb::Keysym) -> bool {
+        let confirm_or_cancel = matches!(
+            keysym,
+            xkb::Keysym::Return | xkb::Keysym::KP_Enter | xkb::Keysym::Escape
+        );
+        match self.mode {
+            Mode::Adjust if self.adjust_mode.input.is_some() => !confirm_or_cancel,
+            Mode::Adjust => matches!(
+                keysym,
+                xkb::Keysym::Left | xkb::Keysym::Right | xkb::Keysym::Up | xkb::Keysym::Down
+            ),
+            // NOTE: 文本中的 Return 是换行
+            Mode::ShowResult => self.edit_mode.caret.is_some() && keysym != xkb::Keysym::Escape,
+            _ => false,
+        }
+    }
+
+    /// 处理按下的按键，按住可重复的按键时由主循环重复调用
+    pub fn key_pressed(&mut self, key: u32) {
+        // NOTE: xkb 键码为 evdev 键码加 8
+        let keycode = xkb::Keycode::new(key + 8);
+        let Some(xkb_state) = self.wayland_ctx.xkb_state.as_ref() else {
+            return;
+        };
+        let keysym = xkb_state.key_get_one_sym(keycode);
+        let mut text = xkb_state.key_get_utf8(keycode);
+        // NOTE: 死键和 Compose 序列，序列未完成或被取消时不处理按键
+        if let Some(compose) = self.wayland_ctx.compose_state.as_mut()
+            && compose.feed(keysym) == xkb::compose::FeedResult::Accepted
+        {
+            match compose.status() {
+                xkb::compose::Status::Composing => return,
+                xkb::compose::Status::Composed => {
+                    text = compose.utf8().unwrap_or_default();
+                    compose.reset();
+                }
+                xkb::compose::Status::Cancelled => {
+                    compose.reset();
+                    return;
+                }
+                xkb::compose::Status::Nothing => {}
+            }
+        }
+        let modifier = |name| xkb_state.mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE);
+        let (ctrl, shift, alt) = (
+            modifier(xkb::MOD_NAME_CTRL),
+            modifier(xkb::MOD_NAME_SHIFT),
+            modifier(xkb::MOD_NAME_ALT),
+        );
+        debug!("key {} keysym {:?}", key, keysym);
+        let keys = &self.cli.keys;
+        match self.mode {
+            Mode::ShowResult => {
+                // NOTE: 输入文本时按键优先交给文本
+                if self.edit_mode.key(keysym, &text) || self.edit_mode.shortcut(keysym, ctrl, shift)
+                {
+                    return;
+                }
+                if keysym == keys.back.0 {
+                    self.edit_mode.reset();
+                    self.mode = Mode::PreSelect;
+                } else if keysym == keys.confirm.0 {
+                    self.mode = Mode::Output;
+                }
+            }
+            Mode::Adjust => {
+                // NOTE: 方向键和输入的坐标优先
+                if self.adjust_mode.key(keysym, &text, shift, alt) {
+                    return;
+                }
+                if keysym == keys.confirm.0 {
+                    let region = self.adjust_mode.region;
+                    self.confirm_selection(region);
+                } else if keysym == keys.back.0 {
+                    self.mode = Mode::PreSelect;
+                }
+            }
+            Mode::Await | Mode::Measure if self.cli.measure => {
+                if self.measure_mode.key(keysym, ctrl) {
+                    return;
+                }
+                if keysym == keys.exit.0 {
+                    self.mode = Mode::Exit;
+                }
+            }
+            Mode::Await if self.cli.pick_color => {
+                if keysym == keys.confirm.0 {
+                    self.mode = Mode::Output;
+                } else if keysym == keys.exit.0 {
+                    self.mode = Mode::Exit;
+                }
+            }
+            Mode::Await => {
+                if keysym == keys.full_screen.0 {
+                    debug!("full screen");
+                    self.result_mode.full_screen = true;
+                    self.mode = Mode::Output;
+                } else if keysym == keys.exit.0 {
+                    self.mode = Mode::Exit;
+                }
+            }
+            _ => {}
         }
     }
 }
 
 #[allow(unused_variables)]
-impl Dispatch<ZwlrScreencopyFrameV1, ()> for FoamShot {
+impl Dispatch<ZwlrScreencopyFrameV1, usize> for FoamShot {
     fn event(
         state: &mut Self,
         proxy: &ZwlrScreencopyFrameV1,
         event: <ZwlrScreencopyFrameV1 as wayland_client::Proxy>::Event,
-        data: &(),
+        data: &usize,
         conn: &wayland_client::Connection,
         qh: &wayland_client::QueueHandle<Self>,
     ) {
@@ -260,25 +602,22 @@ impl Dispatch<ZwlrScreencopyFrameV1, ()> for FoamShot {
                 stride,
             } => {
                 debug!(
-                    "format:{:?}, width:{}, height:{}, stride:{}",
-                    format, width, height, stride
+                    "output:{}, format:{:?}, width:{}, height:{}, stride:{}",
+                    data, format, width, height, stride
                 );
+                let format = format.into_result().unwrap();
                 let buffer = Some(
                     state
                         .wayland_ctx
-                        .create_buffer(
-                            width as i32,
-                            height as i32,
-                            stride as i32,
-                            format.into_result().unwrap(),
-                        )
+                        .create_buffer(width as i32, height as i32, stride as i32, format)
                         .ok()
                         .unwrap()
                         .0,
                 );
                 match state.mode {
                     mode::Mode::Freeze(mode::CopyHook::Request) => {
-                        state.freeze_mode.buffer = buffer;
+                        state.freeze_mode.buffers[*data] = buffer;
+                        state.freeze_mode.formats[*data] = format;
                     }
                     // Mode::Output(CopyHook::Request) => {
                     //     state.result_mode.buffer = buffer;
@@ -288,11 +627,18 @@ impl Dispatch<ZwlrScreencopyFrameV1, ()> for FoamShot {
             }
             zwlr_screencopy_frame_v1::Event::BufferDone { .. } => match state.mode {
                 Mode::Freeze(CopyHook::Request) => {
-                    proxy.copy(state.freeze_mode.buffer.as_mut().unwrap().wl_buffer());
+                    proxy.copy(
+                        state.freeze_mode.buffers[*data]
+                            .as_mut()
+                            .unwrap()
+                            .wl_buffer(),
+                    );
 
-                    state.result_mode.screencopy_frame = Some(proxy.clone());
-                    state.mode = mode::Mode::Freeze(mode::CopyHook::BufferDone);
-                    debug!("set BeforeFreeze");
+                    state.freeze_mode.buffer_done_count += 1;
+                    if state.freeze_mode.all_buffer_done() {
+                        state.mode = mode::Mode::Freeze(mode::CopyHook::BufferDone);
+                        debug!("set BeforeFreeze");
+                    }
                 }
                 // Mode::Output(CopyHook::Request) => {
                 //     proxy.copy(state.result_mode.buffer.as_mut().unwrap().wl_buffer());
@@ -301,12 +647,20 @@ impl Dispatch<ZwlrScreencopyFrameV1, ()> for FoamShot {
                 // }
                 _ => (),
             },
+            zwlr_screencopy_frame_v1::Event::Flags {
+                flags: wayland_client::WEnum::Value(flags),
+            } => {
+                state.freeze_mode.y_inverts[*data] =
+                    flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert);
+            }
             // NOTE: screen is freeze now
             zwlr_screencopy_frame_v1::Event::Ready { .. } => {
                 // state.mode = mode::Mode::BeforeFreeze;
 
+                // NOTE: 各输出的帧可能先于其他输出的 BufferDone 就绪
+                state.freeze_mode.ready_count += 1;
                 match state.mode {
-                    Mode::Freeze(CopyHook::BufferDone) => {
+                    Mode::Freeze(CopyHook::BufferDone) if state.freeze_mode.all_ready() => {
                         state.mode = mode::Mode::Freeze(mode::CopyHook::Ready);
                         debug!("set Freeze");
                     }
@@ -325,6 +679,138 @@ impl Dispatch<ZwlrScreencopyFrameV1, ()> for FoamShot {
     }
 }
 
+impl Dispatch<zxdg_output_v1::ZxdgOutputV1, usize> for FoamShot {
+    fn event(
+        state: &mut Self,
+        _proxy: &zxdg_output_v1::ZxdgOutputV1,
+        event: <zxdg_output_v1::ZxdgOutputV1 as Proxy>::Event,
+        data: &usize,
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        let output = &mut state.wayland_ctx.foam_outputs[*data];
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                output.global_x = x;
+                output.global_y = y;
+            }
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                output.logical_width = Some(width);
+                output.logical_height = Some(height);
+            }
+            // NOTE: 优先使用 wl_output v4 的名称
+            zxdg_output_v1::Event::Name { name } => {
+                output.name.get_or_insert(name);
+            }
+            zxdg_output_v1::Event::Description { description } => {
+                output.description.get_or_insert(description);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<wp_fractional_scale_v1::WpFractionalScaleV1, usize> for FoamShot {
+    fn event(
+        state: &mut Self,
+        _proxy: &wp_fractional_scale_v1::WpFractionalScaleV1,
+        event: <wp_fractional_scale_v1::WpFractionalScaleV1 as Proxy>::Event,
+        data: &usize,
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        if let wp_fractional_scale_v1::Event::PreferredScale { scale } = event {
+            // NOTE: scale 以 120 为分母
+            debug!("output {} preferred scale {}/120", data, scale);
+            state.wayland_ctx.foam_outputs[*data].preferred_scale = Some(scale as f64 / 120.0);
+        }
+    }
+}
+
+impl Dispatch<wl_data_source::WlDataSource, ()> for FoamShot {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_data_source::WlDataSource,
+        event: <wl_data_source::WlDataSource as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_source::Event::Send { mime_type, fd } => {
+                if let Some(clipboard) = &state.result_mode.clipboard {
+                    clipboard.send(&mime_type, fd);
+                }
+            }
+            // NOTE: 其他程序接管了剪贴板
+            wl_data_source::Event::Cancelled => {
+                state.mode = Mode::Exit;
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<zwlr_data_control_source_v1::ZwlrDataControlSourceV1, ()> for FoamShot {
+    fn event(
+        state: &mut Self,
+        _proxy: &zwlr_data_control_source_v1::ZwlrDataControlSourceV1,
+        event: <zwlr_data_control_source_v1::ZwlrDataControlSourceV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_source_v1::Event::Send { mime_type, fd } => {
+                if let Some(clipboard) = &state.result_mode.clipboard {
+                    clipboard.send(&mime_type, fd);
+                }
+            }
+            // NOTE: 其他程序接管了剪贴板
+            zwlr_data_control_source_v1::Event::Cancelled => {
+                state.mode = Mode::Exit;
+            }
+            _ => (),
+        }
+    }
+}
+
+// NOTE: 不读取剪贴板，只需要接收新建的 offer 对象
+impl Dispatch<wl_data_device::WlDataDevice, ()> for FoamShot {
+    event_created_child!(FoamShot, wl_data_device::WlDataDevice, [
+        wl_data_device::EVT_DATA_OFFER_OPCODE => (wl_data_offer::WlDataOffer, ())
+    ]);
+
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_data_device::WlDataDevice,
+        _event: <wl_data_device::WlDataDevice as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, ()> for FoamShot {
+    event_created_child!(FoamShot, zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (zwlr_data_control_offer_v1::ZwlrDataControlOfferV1, ())
+    ]);
+
+    fn event(
+        _state: &mut Self,
+        proxy: &zwlr_data_control_device_v1::ZwlrDataControlDeviceV1,
+        event: <zwlr_data_control_device_v1::ZwlrDataControlDeviceV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        if let zwlr_data_control_device_v1::Event::Finished = event {
+            proxy.destroy();
+        }
+    }
+}
+
 impl ShmHandler for FoamShot {
     fn shm_state(&mut self) -> &mut smithay_client_toolkit::shm::Shm {
         self.wayland_ctx.shm.as_mut().unwrap()
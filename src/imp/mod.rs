@@ -1,9 +1,17 @@
 use wayland_client::globals::GlobalListContents;
-use wayland_client::protocol::{wl_compositor, wl_registry, wl_seat, wl_surface};
+use wayland_client::protocol::{
+    wl_compositor, wl_data_device_manager, wl_data_offer, wl_registry, wl_seat, wl_surface,
+};
 use wayland_client::{Dispatch, Proxy};
 use wayland_protocols::wp::cursor_shape::v1::client::{
     wp_cursor_shape_device_v1, wp_cursor_shape_manager_v1,
 };
+use wayland_protocols::wp::fractional_scale::v1::client::wp_fractional_scale_manager_v1;
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1;
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_manager_v1, zwlr_data_control_offer_v1,
+};
 use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1;
 use wayland_protocols_wlr::screencopy::v1::client::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1;
 
@@ -115,3 +123,107 @@ impl Dispatch<wl_compositor::WlCompositor, ()> for FoamShot {
         // todo!()
     }
 }
+
+impl Dispatch<zxdg_output_manager_v1::ZxdgOutputManagerV1, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &zxdg_output_manager_v1::ZxdgOutputManagerV1,
+        _event: <zxdg_output_manager_v1::ZxdgOutputManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
+
+impl Dispatch<wp_viewporter::WpViewporter, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wp_viewporter::WpViewporter,
+        _event: <wp_viewporter::WpViewporter as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
+
+impl Dispatch<wp_viewport::WpViewport, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wp_viewport::WpViewport,
+        _event: <wp_viewport::WpViewport as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
+
+impl Dispatch<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        _event: <wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
+
+impl Dispatch<wl_data_device_manager::WlDataDeviceManager, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_data_device_manager::WlDataDeviceManager,
+        _event: <wl_data_device_manager::WlDataDeviceManager as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
+
+impl Dispatch<wl_data_offer::WlDataOffer, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_data_offer::WlDataOffer,
+        _event: <wl_data_offer::WlDataOffer as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
+
+impl Dispatch<zwlr_data_control_manager_v1::ZwlrDataControlManagerV1, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+        _event: <zwlr_data_control_manager_v1::ZwlrDataControlManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
+
+impl Dispatch<zwlr_data_control_offer_v1::ZwlrDataControlOfferV1, ()> for FoamShot {
+    fn event(
+        _state: &mut Self,
+        _proxy: &zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+        _event: <zwlr_data_control_offer_v1::ZwlrDataControlOfferV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &wayland_client::Connection,
+        _qh: &wayland_client::QueueHandle<Self>,
+    ) {
+        // todo!()
+    }
+}
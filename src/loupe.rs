@@ -0,0 +1,174 @@
+use std::f64::consts::PI;
+
+use cairo::{Context, FontSlant, FontWeight, ImageSurface};
+
+use crate::config::Color;
+use crate::mode::freeze_mode::FreezeMode;
+use crate::mode::result_mode::ResultMode;
+use crate::wayland_ctx::WaylandCtx;
+
+/// 放大的像素数，奇数使光标所在的像素位于中心
+const PIXELS: i32 = 15;
+/// 每个像素放大后的边长，逻辑像素
+const ZOOM: f64 = 8.0;
+/// 放大镜与光标的距离
+const OFFSET: f64 = 24.0;
+const LABEL_HEIGHT: f64 = 38.0;
+
+const BACKGROUND: Color = Color::rgba(0x2b, 0x2b, 0x2b, 0xe6);
+const GRID: Color = Color::rgba(0x80, 0x80, 0x80, 0x60);
+const CROSSHAIR: Color = Color::rgba(0xe5, 0x39, 0x35, 255);
+const TEXT: Color = Color::rgba(0xee, 0xee, 0xee, 255);
+
+/// 光标附近冻结画面的放大镜
+pub struct Loupe {
+    /// 光标位置，全局逻辑坐标
+    pub pos: (f64, f64),
+    /// 以光标所在像素为中心的 PIXELS x PIXELS 像素
+    pub patch: ImageSurface,
+    /// 光标所在的物理像素，相对于所在输出的左上角
+    pub physical: (i32, i32),
+    /// 光标所在像素的颜色
+    pub rgb: (u8, u8, u8),
+    /// 光标所在的输出，放大镜不超出该输出
+    pub bounds: (f64, f64, f64, f64),
+}
+
+impl Loupe {
+    /// 从冻结画面中取光标附近的像素
+    pub fn capture(
+        result_mode: &ResultMode,
+        wl_ctx: &mut WaylandCtx,
+        freeze_mode: &mut FreezeMode,
+        pos: (f64, f64),
+    ) -> Option<Self> {
+        let output = wl_ctx
+            .foam_outputs
+            .iter()
+            .find(|o| o.intersect((pos.0, pos.1, 1.0, 1.0)).is_some())?;
+        let scale = output.buffer_scale();
+        let bounds = output.global_rect();
+        let (ox, oy) = (output.global_x as f64, output.global_y as f64);
+        // NOTE: 对齐到物理像素，使光标所在的像素正好是中心的像素
+        let half = (PIXELS / 2) as f64;
+        let physical = (
+            ((pos.0 - ox) * scale).floor(),
+            ((pos.1 - oy) * scale).floor(),
+        );
+        let left = ox + (physical.0 - half) / scale;
+        let top = oy + (physical.1 - half) / scale;
+        let size = PIXELS as f64 / scale;
+        let patch = result_mode.composite(wl_ctx, freeze_mode, (left, top, size, size))?;
+        // NOTE: 颜色直接从 screencopy buffer 按其像素格式读取
+        let rgb = freeze_mode.pixel(wl_ctx, pos)?;
+        Some(Self {
+            pos,
+            patch,
+            physical: (physical.0 as i32, physical.1 as i32),
+            rgb,
+            bounds,
+        })
+    }
+
+    /// 放大镜和标签占据的矩形，放在光标右下方，超出输出时翻到另一侧
+    pub fn rect(&self) -> (f64, f64, f64, f64) {
+        let (bx, by, bw, bh) = self.bounds;
+        let size = PIXELS as f64 * ZOOM;
+        let (width, height) = (size, size + LABEL_HEIGHT);
+        let mut x = self.pos.0 + OFFSET;
+        if x + width > bx + bw {
+            x = self.pos.0 - OFFSET - width;
+        }
+        let mut y = self.pos.1 + OFFSET;
+        if y + height > by + bh {
+            y = self.pos.1 - OFFSET - height;
+        }
+        (x, y, width, height)
+    }
+
+    /// 在 ctx（全局逻辑坐标）中绘制
+    pub fn draw(&self, ctx: &Context) {
+        let (x, y, size, _) = self.rect();
+        let radius = size / 2.0;
+        let (cx, cy) = (x + radius, y + radius);
+        ctx.save().unwrap();
+
+        // 放大的像素，裁剪为圆形
+        ctx.save().unwrap();
+        ctx.arc(cx, cy, radius, 0.0, 2.0 * PI);
+        ctx.clip();
+        ctx.translate(x, y);
+        ctx.scale(
+            size / self.patch.width() as f64,
+            size / self.patch.height() as f64,
+        );
+        ctx.set_source_surface(&self.patch, 0.0, 0.0).unwrap();
+        ctx.source().set_filter(cairo::Filter::Nearest);
+        ctx.paint().unwrap();
+        ctx.restore().unwrap();
+
+        // 像素网格
+        ctx.save().unwrap();
+        ctx.arc(cx, cy, radius, 0.0, 2.0 * PI);
+        ctx.clip();
+        GRID.set_source(ctx);
+        ctx.set_line_width(1.0);
+        let cell = size / PIXELS as f64;
+        for i in 1..PIXELS {
+            let offset = i as f64 * cell;
+            ctx.move_to(x + offset, y);
+            ctx.line_to(x + offset, y + size);
+            ctx.move_to(x, y + offset);
+            ctx.line_to(x + size, y + offset);
+        }
+        ctx.stroke().unwrap();
+
+        // 光标所在的像素
+        CROSSHAIR.set_source(ctx);
+        ctx.set_line_width(2.0);
+        let center = (PIXELS / 2) as f64 * cell;
+        ctx.rectangle(x + center, y + center, cell, cell);
+        ctx.stroke().unwrap();
+        ctx.restore().unwrap();
+
+        BACKGROUND.set_source(ctx);
+        ctx.set_line_width(2.0);
+        ctx.arc(cx, cy, radius, 0.0, 2.0 * PI);
+        ctx.stroke().unwrap();
+
+        // 坐标和颜色
+        let (r, g, b) = self.rgb;
+        let lines = [
+            // NOTE: 缩放大于 1 时多个物理像素对应同一个逻辑坐标
+            format!(
+                "{}, {}  physical {}, {}",
+                self.pos.0.floor(),
+                self.pos.1.floor(),
+                self.physical.0,
+                self.physical.1
+            ),
+            format!("#{:02x}{:02x}{:02x}  rgb({}, {}, {})", r, g, b, r, g, b),
+        ];
+        ctx.select_font_face("Monospace", FontSlant::Normal, FontWeight::Normal);
+        ctx.set_font_size(12.0);
+        let width = lines
+            .iter()
+            .map(|line| ctx.text_extents(line).unwrap().x_advance())
+            .fold(0.0, f64::max)
+            + 30.0;
+        let label_x = cx - width / 2.0;
+        let label_y = y + size + 4.0;
+        BACKGROUND.set_source(ctx);
+        ctx.rectangle(label_x, label_y, width, LABEL_HEIGHT - 4.0);
+        ctx.fill().unwrap();
+        ctx.set_source_rgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+        ctx.rectangle(label_x + width - 14.0, label_y + 4.0, 10.0, 10.0);
+        ctx.fill().unwrap();
+        TEXT.set_source(ctx);
+        for (i, line) in lines.iter().enumerate() {
+            ctx.move_to(label_x + 6.0, label_y + 14.0 + i as f64 * 15.0);
+            ctx.show_text(line).unwrap();
+        }
+        ctx.restore().unwrap();
+    }
+}
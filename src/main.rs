@@ -1,7 +1,18 @@
+mod annotation;
+mod clipboard;
+mod color_format;
 mod config;
+mod encoder;
+mod foam_output;
 mod foam_shot;
 mod imp;
+mod loupe;
 mod mode;
+mod path_template;
+mod project;
+mod redact;
+mod snap;
+mod toplevels;
 mod wayland_ctx;
 fn main() {
     env_logger::init();
@@ -0,0 +1,238 @@
+use log::debug;
+use wayland_protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
+use xkbcommon::xkb;
+
+/// 控制柄的边长，也是点击控制柄的容差，逻辑像素
+const HANDLE: f64 = 10.0;
+/// 选区的最小边长
+const MIN_SIZE: f64 = 1.0;
+/// 输入坐标时接受的字符
+const GEOMETRY_CHARS: &str = "0123456789.,-xX ";
+
+/// 正在进行的拖动
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Grab {
+    /// 移动选区，last 为上一次的鼠标位置
+    Move { last: (f64, f64) },
+    /// 拖动边或角，-1 为左（上）边，1 为右（下）边，0 为不移动该方向
+    Resize { edge_x: i8, edge_y: i8 },
+}
+
+/// 松开鼠标后调整选区，拖动控制柄缩放，拖动内部移动，确认后才截图
+#[derive(Debug, Default)]
+pub struct AdjustMode {
+    /// 选区，全局逻辑坐标
+    pub region: (f64, f64, f64, f64),
+    /// 所有输出的外接矩形，选区不超出该范围
+    pub bounds: (f64, f64, f64, f64),
+    pub grab: Option<Grab>,
+    /// 当前的鼠标形状，变化时才重新设置
+    pub cursor: Option<Shape>,
+    /// 正在输入的 `x,y WxH`
+    pub input: Option<String>,
+    /// 需要重绘选择层
+    pub dirty: bool,
+}
+
+impl AdjustMode {
+    pub fn begin(&mut self, region: (f64, f64, f64, f64), bounds: (f64, f64, f64, f64)) {
+        debug!("adjust region {:?}", region);
+        self.region = region;
+        self.bounds = bounds;
+        self.grab = None;
+        self.cursor = None;
+        self.input = None;
+        self.dirty = true;
+    }
+
+    /// 选区四角和四边中点的控制柄，(edge_x, edge_y, 位置)
+    pub fn handles(&self) -> [(i8, i8, (f64, f64)); 8] {
+        let (x, y, w, h) = self.region;
+        let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+        [
+            (-1, -1, (x, y)),
+            (0, -1, (cx, y)),
+            (1, -1, (x + w, y)),
+            (1, 0, (x + w, cy)),
+            (1, 1, (x + w, y + h)),
+            (0, 1, (cx, y + h)),
+            (-1, 1, (x, y + h)),
+            (-1, 0, (x, cy)),
+        ]
+    }
+
+    /// pos 处的拖动方式，不在选区上时为 None
+    fn grab_at(&self, pos: (f64, f64)) -> Option<Grab> {
+        if let Some(&(edge_x, edge_y, _)) = self
+            .handles()
+            .iter()
+            .find(|(_, _, (hx, hy))| (pos.0 - hx).abs() <= HANDLE && (pos.1 - hy).abs() <= HANDLE)
+        {
+            return Some(Grab::Resize { edge_x, edge_y });
+        }
+        let (x, y, w, h) = self.region;
+        (pos.0 >= x && pos.0 <= x + w && pos.1 >= y && pos.1 <= y + h)
+            .then_some(Grab::Move { last: pos })
+    }
+
+    /// 鼠标按下，返回是否按在选区上；按在选区外时由调用方重新框选
+    pub fn press(&mut self, pos: (f64, f64)) -> bool {
+        self.grab = self.grab_at(pos);
+        self.grab.is_some()
+    }
+
+    pub fn motion(&mut self, pos: (f64, f64)) {
+        let Some(grab) = self.grab.as_mut() else {
+            return;
+        };
+        let (x, y, w, h) = self.region;
+        let (bx, by, bw, bh) = self.bounds;
+        match grab {
+            Grab::Move { last } => {
+                let dx = (pos.0 - last.0).clamp(bx - x, bx + bw - x - w);
+                let dy = (pos.1 - last.1).clamp(by - y, by + bh - y - h);
+                self.region = (x + dx, y + dy, w, h);
+                *last = pos;
+            }
+            &mut Grab::Resize { edge_x, edge_y } => {
+                let (px, py) = (pos.0.clamp(bx, bx + bw), pos.1.clamp(by, by + bh));
+                let (mut left, mut top, mut right, mut bottom) = (x, y, x + w, y + h);
+                // NOTE: 拖过对边时停在对边，不翻转选区
+                match edge_x {
+                    -1 => left = px.min(right - MIN_SIZE),
+                    1 => right = px.max(left + MIN_SIZE),
+                    _ => {}
+                }
+                match edge_y {
+                    -1 => top = py.min(bottom - MIN_SIZE),
+                    1 => bottom = py.max(top + MIN_SIZE),
+                    _ => {}
+                }
+                self.region = (left, top, right - left, bottom - top);
+            }
+        }
+        self.dirty = true;
+    }
+
+    pub fn release(&mut self) {
+        self.grab = None;
+    }
+
+    /// 处理按键，返回是否已处理。
+    /// 方向键移动 1px，按住 Shift 移动 10px，按住 Alt 时改变宽高；
+    /// 输入数字或 `-` 开始输入 `x,y WxH`，Return 应用，Escape 放弃
+    pub fn key(&mut self, keysym: xkb::Keysym, text: &str, shift: bool, alt: bool) -> bool {
+        if let Some(input) = self.input.as_mut() {
+            match keysym {
+                xkb::Keysym::Escape => self.input = None,
+                xkb::Keysym::Return | xkb::Keysym::KP_Enter => match parse_geometry(input) {
+                    Some(region) => {
+                        self.region = self.clamp(region);
+                        self.input = None;
+                    }
+                    None => debug!("invalid geometry `{}`", input),
+                },
+                xkb::Keysym::BackSpace => {
+                    input.pop();
+                }
+                _ => input.extend(text.chars().filter(|c| GEOMETRY_CHARS.contains(*c))),
+            }
+            self.dirty = true;
+            return true;
+        }
+
+        let step = if shift { 10.0 } else { 1.0 };
+        let (dx, dy) = match keysym {
+            xkb::Keysym::Left => (-step, 0.0),
+            xkb::Keysym::Right => (step, 0.0),
+            xkb::Keysym::Up => (0.0, -step),
+            xkb::Keysym::Down => (0.0, step),
+            _ => {
+                // NOTE: 左侧或上方的输出可能有负坐标
+                if text.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+                    self.input = Some(text.to_string());
+                    self.dirty = true;
+                    return true;
+                }
+                return false;
+            }
+        };
+        let (x, y, w, h) = self.region;
+        self.region = if alt {
+            // NOTE: 左上角不动，改变右下角
+            let (bx, by, bw, bh) = self.bounds;
+            (
+                x,
+                y,
+                (w + dx).clamp(MIN_SIZE, bx + bw - x),
+                (h + dy).clamp(MIN_SIZE, by + bh - y),
+            )
+        } else {
+            self.clamp((x + dx, y + dy, w, h))
+        };
+        self.dirty = true;
+        true
+    }
+
+    /// 将区域限制在所有输出的范围内，超出时优先保持尺寸
+    fn clamp(&self, (x, y, w, h): (f64, f64, f64, f64)) -> (f64, f64, f64, f64) {
+        let (bx, by, bw, bh) = self.bounds;
+        let (w, h) = (w.clamp(MIN_SIZE, bw), h.clamp(MIN_SIZE, bh));
+        (x.clamp(bx, bx + bw - w), y.clamp(by, by + bh - h), w, h)
+    }
+
+    /// 鼠标在 pos 时应显示的形状
+    pub fn cursor_at(&self, pos: (f64, f64)) -> Shape {
+        match self.grab.or_else(|| self.grab_at(pos)) {
+            Some(Grab::Move { .. }) => Shape::Move,
+            Some(Grab::Resize { edge_x, edge_y }) => match (edge_x, edge_y) {
+                (-1, -1) => Shape::NwResize,
+                (1, -1) => Shape::NeResize,
+                (1, 1) => Shape::SeResize,
+                (-1, 1) => Shape::SwResize,
+                (0, _) => Shape::NsResize,
+                _ => Shape::EwResize,
+            },
+            None => Shape::Crosshair,
+        }
+    }
+}
+
+/// 解析 `x,y WxH`，如 `100,200 640x480`
+fn parse_geometry(input: &str) -> Option<(f64, f64, f64, f64)> {
+    let (position, size) = input.trim().split_once(' ')?;
+    let (x, y) = position.split_once(',')?;
+    let (w, h) = size.trim().split_once(['x', 'X'])?;
+    let number = |s: &str| s.trim().parse::<f64>().ok();
+    let (w, h) = (number(w)?, number(h)?);
+    (w > 0.0 && h > 0.0).then_some((number(x)?, number(y)?, w, h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_geometry() {
+        assert_eq!(
+            parse_geometry("100,200 640x480"),
+            Some((100.0, 200.0, 640.0, 480.0))
+        );
+        assert_eq!(
+            parse_geometry("-1920,-80 10X20"),
+            Some((-1920.0, -80.0, 10.0, 20.0))
+        );
+        assert_eq!(parse_geometry(" 0.5,1 2.5x3 "), Some((0.5, 1.0, 2.5, 3.0)));
+    }
+
+    #[test]
+    fn invalid_geometry() {
+        assert_eq!(parse_geometry(""), None);
+        assert_eq!(parse_geometry("100,200"), None);
+        assert_eq!(parse_geometry("100 640x480"), None);
+        assert_eq!(parse_geometry("100,200 640"), None);
+        assert_eq!(parse_geometry("100,200 0x480"), None);
+        assert_eq!(parse_geometry("100,200 -640x480"), None);
+        assert_eq!(parse_geometry("-,200 640x480"), None);
+    }
+}
@@ -0,0 +1,908 @@
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+use cairo::{Context, FontSlant, FontWeight, ImageSurface};
+use log::debug;
+use xkbcommon::xkb;
+
+use super::Mode;
+use crate::annotation::{Annotation, Shape, Style, Tool, rounded_rect};
+use crate::config::{Color, Editor};
+use crate::redact::{Backdrop, Redaction};
+use crate::wayland_ctx::WaylandCtx;
+
+/// 工具栏按钮的边长，逻辑像素
+const BUTTON: f64 = 32.0;
+const PADDING: f64 = 6.0;
+/// 按钮分组之间的间距
+const GROUP_GAP: f64 = 12.0;
+/// 工具栏与选区的距离
+const MARGIN: f64 = 8.0;
+
+/// 选中标注的控制柄边长，也是点击控制柄的容差
+const HANDLE: f64 = 8.0;
+/// 两次点击的最大间隔，视为双击
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// 滚轮一格对应的 axis 值
+const SCROLL_STEP: f64 = 10.0;
+const MIN_WIDTH: f64 = 1.0;
+const MAX_WIDTH: f64 = 64.0;
+
+const TOOLBAR_BACKGROUND: Color = Color::rgba(0x2b, 0x2b, 0x2b, 0xe6);
+const TOOLBAR_HIGHLIGHT: Color = Color::rgba(255, 255, 255, 0x40);
+const TOOLBAR_ICON: Color = Color::rgba(0xee, 0xee, 0xee, 255);
+
+/// 工具栏按钮
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToolbarItem {
+    Tool(Tool),
+    /// 调色板中的颜色
+    Color(usize),
+    /// 可选线宽中的一个
+    Width(usize),
+    /// 文本工具可选的字体族
+    Font(usize),
+    /// 文本工具可选的字号
+    FontSize(usize),
+    /// 打码工具的打码方式
+    Redaction(Redaction),
+    /// 返回重新框选
+    Cancel,
+    /// 保存截图
+    Save,
+}
+
+/// 选择工具正在进行的拖动
+#[derive(Debug, Clone)]
+pub enum Grab {
+    /// 移动选中的标注，last 为上一次的鼠标位置
+    Move { last: (f64, f64) },
+    /// 拖动外接矩形的角缩放，anchor 为对角的不动点，original 为开始缩放时的标注
+    Resize {
+        original: Annotation,
+        anchor: (f64, f64),
+        corner: (f64, f64),
+    },
+}
+
+/// 框选后的标注编辑，在选择层上绘制标注和工具栏，保存时标注绘制到截图上
+pub struct EditMode {
+    pub config: Editor,
+    pub tool: Tool,
+    pub style: Style,
+    /// 选区，全局逻辑坐标
+    pub region: (f64, f64, f64, f64),
+    /// 放置工具栏的输出区域
+    pub bounds: (f64, f64, f64, f64),
+    /// 冻结画面中选区的原始像素，用于预览打码
+    pub base: Option<ImageSurface>,
+    pub annotations: Vec<Annotation>,
+    /// 正在拖动绘制或输入的标注
+    pub drawing: Option<Annotation>,
+    /// 正在输入文本时光标的字节下标
+    pub caret: Option<usize>,
+    /// 选择工具选中的标注下标
+    pub selected: Option<usize>,
+    /// 重新编辑的文本原来的下标，结束输入时放回原处
+    pub editing: Option<usize>,
+    /// 选择工具上一次点击的时间，用于识别双击
+    pub last_click: Option<Instant>,
+    pub grab: Option<Grab>,
+    /// 每次修改前的标注列表，用于撤销
+    pub undo: Vec<Vec<Annotation>>,
+    pub redo: Vec<Vec<Annotation>>,
+    /// 工具栏背景的矩形
+    pub toolbar_rect: (f64, f64, f64, f64),
+    /// 工具栏按钮及其矩形
+    pub toolbar: Vec<(ToolbarItem, (f64, f64, f64, f64))>,
+    /// 累积的滚轮距离，每满一格调整一次线宽
+    pub scroll: f64,
+    /// 需要重绘选择层
+    pub dirty: bool,
+}
+
+impl EditMode {
+    pub fn new(config: Editor) -> Self {
+        Self {
+            tool: Tool::Rectangle,
+            style: Style {
+                color: config.color,
+                width: config.width,
+                font: config.font.clone(),
+                font_size: config.font_size,
+                redaction: Redaction::Pixelate,
+            },
+            config,
+            region: (0.0, 0.0, 0.0, 0.0),
+            bounds: (0.0, 0.0, 0.0, 0.0),
+            base: None,
+            annotations: Vec::new(),
+            drawing: None,
+            caret: None,
+            selected: None,
+            editing: None,
+            last_click: None,
+            grab: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            toolbar_rect: (0.0, 0.0, 0.0, 0.0),
+            toolbar: Vec::new(),
+            scroll: 0.0,
+            dirty: false,
+        }
+    }
+
+    /// 进入编辑，清空之前的标注并在选区旁放置工具栏。base 为选区的截图
+    pub fn begin(
+        &mut self,
+        region: (f64, f64, f64, f64),
+        wl_ctx: &WaylandCtx,
+        base: Option<ImageSurface>,
+    ) {
+        debug!("edit region {:?}", region);
+        self.region = region;
+        self.base = base;
+        self.annotations.clear();
+        self.drawing = None;
+        self.caret = None;
+        self.selected = None;
+        self.editing = None;
+        self.grab = None;
+        self.undo.clear();
+        self.redo.clear();
+
+        // NOTE: 工具栏放在选区底边中点所在的输出内
+        let (x, y, w, h) = region;
+        let anchor = (x + w / 2.0, y + h);
+        self.bounds = wl_ctx
+            .foam_outputs
+            .iter()
+            .find(|o| o.intersect((anchor.0, anchor.1 - 1.0, 1.0, 1.0)).is_some())
+            .or(wl_ctx.foam_outputs.first())
+            .map(|o| o.global_rect())
+            .unwrap_or(region);
+        self.layout_toolbar();
+        self.dirty = true;
+    }
+
+    /// 退出编辑，丢弃标注
+    pub fn reset(&mut self) {
+        self.annotations.clear();
+        self.drawing = None;
+        self.caret = None;
+        self.selected = None;
+        self.editing = None;
+        self.grab = None;
+        self.undo.clear();
+        self.redo.clear();
+        self.base = None;
+        self.toolbar.clear();
+        self.dirty = false;
+    }
+
+    /// 工具栏放在选区下方，放不下时放在上方，再放不下时放在选区内底部。
+    /// 文本和气泡工具显示字体和字号，打码工具显示打码方式，其他工具显示线宽
+    fn layout_toolbar(&mut self) {
+        let mut items: Vec<Vec<ToolbarItem>> = vec![
+            Tool::ALL.into_iter().map(ToolbarItem::Tool).collect(),
+            (0..self.config.palette.len())
+                .map(ToolbarItem::Color)
+                .collect(),
+        ];
+        if self.tool.is_text() {
+            items.push(
+                (0..self.config.fonts.len())
+                    .map(ToolbarItem::Font)
+                    .collect(),
+            );
+            items.push(
+                (0..self.config.font_sizes.len())
+                    .map(ToolbarItem::FontSize)
+                    .collect(),
+            );
+        } else if self.tool == Tool::Redact {
+            items.push(
+                Redaction::ALL
+                    .into_iter()
+                    .map(ToolbarItem::Redaction)
+                    .collect(),
+            );
+        } else {
+            items.push(
+                (0..self.config.widths.len())
+                    .map(ToolbarItem::Width)
+                    .collect(),
+            );
+        }
+        items.push(vec![ToolbarItem::Cancel, ToolbarItem::Save]);
+        items.retain(|group| !group.is_empty());
+        let count: usize = items.iter().map(Vec::len).sum();
+        let width = PADDING * 2.0
+            + BUTTON * count as f64
+            + GROUP_GAP * (items.len().saturating_sub(1)) as f64;
+        let height = BUTTON + PADDING * 2.0;
+
+        let (x, y, w, h) = self.region;
+        let (ox, oy, ow, oh) = self.bounds;
+        let left = (x + w / 2.0 - width / 2.0).min(ox + ow - width).max(ox);
+        let mut top = y + h + MARGIN;
+        if top + height > oy + oh {
+            top = y - MARGIN - height;
+            if top < oy {
+                top = (y + h - MARGIN - height).max(oy);
+            }
+        }
+        self.toolbar_rect = (left, top, width, height);
+
+        self.toolbar.clear();
+        let mut button_x = left + PADDING;
+        for group in items {
+            for item in group {
+                self.toolbar
+                    .push((item, (button_x, top + PADDING, BUTTON, BUTTON)));
+                button_x += BUTTON;
+            }
+            button_x += GROUP_GAP;
+        }
+    }
+
+    fn item_at(&self, pos: (f64, f64)) -> Option<ToolbarItem> {
+        self.toolbar
+            .iter()
+            .find(|(_, rect)| contains(*rect, pos))
+            .map(|(item, _)| *item)
+    }
+
+    /// 鼠标按下，点击工具栏时切换工具或设置，否则开始绘制标注。
+    /// 返回需要切换到的模式
+    pub fn press(&mut self, pos: (f64, f64)) -> Option<Mode> {
+        if contains(self.toolbar_rect, pos) {
+            match self.item_at(pos)? {
+                ToolbarItem::Tool(tool) => {
+                    if tool != self.tool {
+                        self.finish_text();
+                        self.selected = None;
+                    }
+                    self.tool = tool;
+                    self.layout_toolbar();
+                }
+                ToolbarItem::Color(i) => {
+                    self.style.color = self.config.palette[i];
+                    let color = self.style.color;
+                    self.edit_selected(|annotation| annotation.color = color);
+                }
+                ToolbarItem::Width(i) => {
+                    self.style.width = self.config.widths[i];
+                    let width = self.style.width;
+                    self.edit_selected(|annotation| annotation.width = width);
+                }
+                ToolbarItem::Font(i) => {
+                    self.style.font = self.config.fonts[i].clone();
+                    let font = self.style.font.clone();
+                    self.edit_selected(|annotation| {
+                        if let Shape::Text { font: f, .. } | Shape::Bubble { font: f, .. } =
+                            &mut annotation.shape
+                        {
+                            *f = font;
+                        }
+                    });
+                }
+                ToolbarItem::FontSize(i) => {
+                    self.style.font_size = self.config.font_sizes[i];
+                    let font_size = self.style.font_size;
+                    self.edit_selected(|annotation| {
+                        if let Shape::Text { size, .. } | Shape::Bubble { size, .. } =
+                            &mut annotation.shape
+                        {
+                            *size = font_size;
+                        }
+                    });
+                }
+                ToolbarItem::Redaction(redaction) => self.style.redaction = redaction,
+                ToolbarItem::Cancel => {
+                    self.reset();
+                    return Some(Mode::PreSelect);
+                }
+                ToolbarItem::Save => {
+                    self.finish_text();
+                    return Some(Mode::Output);
+                }
+            }
+            self.restyle_text();
+            self.dirty = true;
+            return None;
+        }
+
+        // NOTE: 点击其他位置结束正在输入的文本
+        self.finish_text();
+        if self.tool == Tool::Select {
+            self.select_at(pos);
+            self.dirty = true;
+            return None;
+        }
+        let mut annotation = Annotation::new(self.tool, pos, &self.style);
+        if let Shape::Step { number, .. } = &mut annotation.shape {
+            *number = self.step_count() + 1;
+        }
+        self.drawing = Some(annotation);
+        if self.tool == Tool::Text {
+            self.caret = Some(0);
+        }
+        self.dirty = true;
+        None
+    }
+
+    /// 滚轮调整线宽，向上滚动加粗，正在绘制的标注同时更新
+    pub fn axis(&mut self, value: f64) {
+        self.scroll += value;
+        let steps = (self.scroll / SCROLL_STEP).trunc();
+        if steps == 0.0 {
+            return;
+        }
+        self.scroll -= steps * SCROLL_STEP;
+        self.style.width = (self.style.width - steps).clamp(MIN_WIDTH, MAX_WIDTH);
+        debug!("stroke width {}", self.style.width);
+        if let Some(annotation) = self.drawing.as_mut() {
+            annotation.width = self.style.width;
+        }
+        self.dirty = true;
+    }
+
+    /// 点击选中的标注的角时开始缩放，点击标注时选中并开始移动，双击选中的文本时重新编辑
+    fn select_at(&mut self, pos: (f64, f64)) {
+        let now = Instant::now();
+        let double = self
+            .last_click
+            .replace(now)
+            .is_some_and(|last| now - last <= DOUBLE_CLICK);
+        if double
+            && let Some(i) = self.selected
+            && self.annotations[i].contains(pos)
+            && self.edit_text(i)
+        {
+            self.last_click = None;
+            return;
+        }
+        if let Some(i) = self.selected {
+            let (x, y, w, h) = self.annotations[i].bounds();
+            let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+            if let Some(k) = corners
+                .iter()
+                .position(|c| (pos.0 - c.0).abs() <= HANDLE && (pos.1 - c.1).abs() <= HANDLE)
+            {
+                self.checkpoint();
+                self.grab = Some(Grab::Resize {
+                    original: self.annotations[i].clone(),
+                    anchor: corners[(k + 2) % 4],
+                    corner: corners[k],
+                });
+                return;
+            }
+        }
+        self.selected = self.annotations.iter().rposition(|a| a.contains(pos));
+        if self.selected.is_some() {
+            self.checkpoint();
+            self.grab = Some(Grab::Move { last: pos });
+        }
+    }
+
+    /// 重新打开已完成的文本或气泡进行输入，光标在末尾。不是文本时返回 false
+    fn edit_text(&mut self, i: usize) -> bool {
+        let Some(len) = self.annotations[i].text_mut().map(|text| text.len()) else {
+            return false;
+        };
+        self.checkpoint();
+        self.drawing = Some(self.annotations.remove(i));
+        self.editing = Some(i);
+        self.caret = Some(len);
+        self.selected = None;
+        self.grab = None;
+        self.dirty = true;
+        true
+    }
+
+    /// 修改选中的标注，可以撤销
+    fn edit_selected(&mut self, edit: impl FnOnce(&mut Annotation)) {
+        let Some(i) = self.selected else {
+            return;
+        };
+        self.checkpoint();
+        edit(&mut self.annotations[i]);
+    }
+
+    /// 右键删除 pos 处最上层的标注，之后的步骤标记重新编号
+    pub fn remove_at(&mut self, pos: (f64, f64)) {
+        if self.caret.is_some() || self.drawing.is_some() || self.grab.is_some() {
+            return;
+        }
+        if let Some(i) = self.annotations.iter().rposition(|a| a.contains(pos)) {
+            self.remove(i);
+        }
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.checkpoint();
+        self.annotations.remove(i);
+        self.selected = None;
+        self.renumber_steps();
+        self.dirty = true;
+    }
+
+    /// 加入完成的标注
+    fn add(&mut self, annotation: Annotation) {
+        self.checkpoint();
+        self.annotations.push(annotation);
+    }
+
+    /// 在修改标注前记录，新的修改会清空重做
+    fn checkpoint(&mut self) {
+        self.undo.push(self.annotations.clone());
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) {
+        if let Some(annotations) = self.undo.pop() {
+            self.redo
+                .push(std::mem::replace(&mut self.annotations, annotations));
+            self.selected = None;
+            self.dirty = true;
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(annotations) = self.redo.pop() {
+            self.undo
+                .push(std::mem::replace(&mut self.annotations, annotations));
+            self.selected = None;
+            self.dirty = true;
+        }
+    }
+
+    /// 编辑的快捷键：Ctrl+Z 撤销，Ctrl+Shift+Z 重做，Delete 和 BackSpace 删除选中的标注，
+    /// Return 重新编辑选中的文本。
+    /// 返回是否已处理
+    pub fn shortcut(&mut self, keysym: xkb::Keysym, ctrl: bool, shift: bool) -> bool {
+        if self.grab.is_some() || self.drawing.is_some() {
+            return false;
+        }
+        // NOTE: 按住 Shift 时 keysym 为大写的 Z
+        if ctrl && (keysym == xkb::Keysym::z || keysym == xkb::Keysym::Z) {
+            if shift {
+                self.redo();
+            } else {
+                self.undo();
+            }
+            return true;
+        }
+        if matches!(keysym, xkb::Keysym::Delete | xkb::Keysym::BackSpace)
+            && let Some(i) = self.selected
+        {
+            self.remove(i);
+            return true;
+        }
+        if matches!(keysym, xkb::Keysym::Return | xkb::Keysym::KP_Enter)
+            && let Some(i) = self.selected
+        {
+            return self.edit_text(i);
+        }
+        false
+    }
+
+    fn step_count(&self) -> u32 {
+        self.annotations
+            .iter()
+            .filter(|a| matches!(a.shape, Shape::Step { .. }))
+            .count() as u32
+    }
+
+    /// 按加入的顺序为步骤标记编号
+    fn renumber_steps(&mut self) {
+        let mut next = 1;
+        for annotation in self.annotations.iter_mut() {
+            if let Shape::Step { number, .. } = &mut annotation.shape {
+                *number = next;
+                next += 1;
+            }
+        }
+    }
+
+    pub fn motion(&mut self, pos: (f64, f64)) {
+        if let (Some(i), Some(grab)) = (self.selected, self.grab.as_mut()) {
+            match grab {
+                Grab::Move { last } => {
+                    self.annotations[i].translate((pos.0 - last.0, pos.1 - last.1));
+                    *last = pos;
+                }
+                Grab::Resize {
+                    original,
+                    anchor,
+                    corner,
+                } => {
+                    let ratio =
+                        |p: f64, a: f64, c: f64| if c == a { 1.0 } else { (p - a) / (c - a) };
+                    let scale = (
+                        ratio(pos.0, anchor.0, corner.0),
+                        ratio(pos.1, anchor.1, corner.1),
+                    );
+                    let mut annotation = original.clone();
+                    annotation.scale(*anchor, scale);
+                    self.annotations[i] = annotation;
+                }
+            }
+            self.dirty = true;
+            return;
+        }
+        if self.caret.is_some() {
+            return;
+        }
+        if let Some(annotation) = self.drawing.as_mut() {
+            annotation.drag_to(pos);
+            self.dirty = true;
+        }
+    }
+
+    pub fn release(&mut self) {
+        if self.grab.take().is_some() {
+            // NOTE: 只是点击选中时没有修改，丢弃按下时的记录
+            if self.undo.last() == Some(&self.annotations) {
+                self.undo.pop();
+            }
+            self.dirty = true;
+            return;
+        }
+        // NOTE: 文本在输入结束后才加入标注
+        if self.caret.is_some() {
+            return;
+        }
+        // NOTE: 气泡拖出尾巴后开始输入文字
+        if let Some(annotation) = self.drawing.as_ref()
+            && let Shape::Bubble { .. } = annotation.shape
+        {
+            self.caret = Some(0);
+            self.dirty = true;
+            return;
+        }
+        if let Some(annotation) = self.drawing.take() {
+            if !annotation.is_empty() {
+                self.add(annotation);
+            }
+            self.dirty = true;
+        }
+    }
+
+    /// 正在输入文本时处理按键，返回是否已处理。
+    /// text 为按键在当前键盘布局和修饰键下产生的字符
+    pub fn key(&mut self, keysym: xkb::Keysym, text: &str) -> bool {
+        let Some(caret) = self.caret else {
+            return false;
+        };
+        if keysym == xkb::Keysym::Escape {
+            self.finish_text();
+            return true;
+        }
+        let Some(content) = self.drawing.as_mut().and_then(Annotation::text_mut) else {
+            return false;
+        };
+
+        let prev = content[..caret].char_indices().next_back().map(|(i, _)| i);
+        let next = content[caret..]
+            .chars()
+            .next()
+            .map(|c| caret + c.len_utf8());
+        let line_start = content[..caret].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = content[caret..]
+            .find('\n')
+            .map_or(content.len(), |i| caret + i);
+
+        let caret = match keysym {
+            xkb::Keysym::BackSpace => match prev {
+                Some(prev) => {
+                    content.replace_range(prev..caret, "");
+                    prev
+                }
+                None => caret,
+            },
+            xkb::Keysym::Delete => {
+                if let Some(next) = next {
+                    content.replace_range(caret..next, "");
+                }
+                caret
+            }
+            xkb::Keysym::Left => prev.unwrap_or(caret),
+            xkb::Keysym::Right => next.unwrap_or(caret),
+            xkb::Keysym::Home => line_start,
+            xkb::Keysym::End => line_end,
+            xkb::Keysym::Return | xkb::Keysym::KP_Enter => {
+                content.insert(caret, '\n');
+                caret + 1
+            }
+            _ => {
+                // NOTE: Ctrl 等组合键产生控制字符，忽略
+                let text: String = text.chars().filter(|c| !c.is_control()).collect();
+                content.insert_str(caret, &text);
+                caret + text.len()
+            }
+        };
+        self.caret = Some(caret);
+        self.dirty = true;
+        true
+    }
+
+    /// 结束文本输入，非空的文本加入标注
+    fn finish_text(&mut self) {
+        if self.caret.take().is_none() {
+            return;
+        }
+        // NOTE: 重新编辑时已在打开前记录，放回原来的位置，清空文字等同于删除
+        if let Some(i) = self.editing.take() {
+            if let Some(annotation) = self.drawing.take()
+                && !annotation.is_empty()
+            {
+                self.annotations.insert(i, annotation);
+            }
+            if self.undo.last() == Some(&self.annotations) {
+                self.undo.pop();
+            }
+            self.dirty = true;
+            return;
+        }
+        if let Some(annotation) = self.drawing.take()
+            && !annotation.is_empty()
+        {
+            self.add(annotation);
+        }
+        self.dirty = true;
+    }
+
+    /// 输入中的文本和气泡跟随工具栏中的颜色、字体和字号
+    fn restyle_text(&mut self) {
+        if self.caret.is_none() {
+            return;
+        }
+        if let Some(annotation) = self.drawing.as_mut()
+            && let Shape::Text { font, size, .. } | Shape::Bubble { font, size, .. } =
+                &mut annotation.shape
+        {
+            *font = self.style.font.clone();
+            *size = self.style.font_size;
+            annotation.color = self.style.color;
+        }
+    }
+
+    /// 在选择层上绘制标注（裁剪到选区内）和工具栏，ctx 为全局逻辑坐标
+    pub fn draw(&self, ctx: &Context) {
+        let (x, y, w, h) = self.region;
+        ctx.save().unwrap();
+        ctx.rectangle(x, y, w, h);
+        ctx.clip();
+        let backdrop = self.base.as_ref().map(|image| Backdrop {
+            image,
+            origin: (x, y),
+            scale: image.width() as f64 / w,
+        });
+        // NOTE: 先在选区内绘制原始截图，荧光笔的正片叠底才能与保存结果一致
+        if let Some(backdrop) = backdrop.as_ref() {
+            ctx.save().unwrap();
+            ctx.translate(x, y);
+            ctx.scale(1.0 / backdrop.scale, 1.0 / backdrop.scale);
+            ctx.set_source_surface(backdrop.image, 0.0, 0.0).unwrap();
+            ctx.paint().unwrap();
+            ctx.restore().unwrap();
+        }
+        for annotation in self.annotations.iter().chain(self.drawing.as_ref()) {
+            annotation.draw(ctx, backdrop.as_ref());
+        }
+        if let (Some(annotation), Some(caret)) = (self.drawing.as_ref(), self.caret)
+            && let Some(((caret_x, caret_y), height)) = annotation.caret(ctx, caret)
+        {
+            annotation.color.set_source(ctx);
+            ctx.set_line_width(1.5);
+            ctx.move_to(caret_x, caret_y);
+            ctx.line_to(caret_x, caret_y + height);
+            ctx.stroke().unwrap();
+        }
+        ctx.restore().unwrap();
+
+        if let Some(annotation) = self.selected.and_then(|i| self.annotations.get(i)) {
+            draw_selection(ctx, annotation.bounds());
+        }
+        self.draw_toolbar(ctx);
+    }
+
+    fn draw_toolbar(&self, ctx: &Context) {
+        if self.toolbar.is_empty() {
+            return;
+        }
+        ctx.save().unwrap();
+        TOOLBAR_BACKGROUND.set_source(ctx);
+        rounded_rect(ctx, self.toolbar_rect, 6.0);
+        ctx.fill().unwrap();
+
+        let icon_style = Style {
+            color: TOOLBAR_ICON,
+            width: 2.0,
+            ..self.style.clone()
+        };
+        for &(item, rect) in self.toolbar.iter() {
+            let (bx, by, bw, bh) = rect;
+            let center = (bx + bw / 2.0, by + bh / 2.0);
+            let (cx, cy) = center;
+            let selected = match item {
+                ToolbarItem::Tool(tool) => tool == self.tool,
+                ToolbarItem::Color(i) => self.config.palette[i] == self.style.color,
+                ToolbarItem::Width(i) => self.config.widths[i] == self.style.width,
+                ToolbarItem::Font(i) => self.config.fonts[i] == self.style.font,
+                ToolbarItem::FontSize(i) => self.config.font_sizes[i] == self.style.font_size,
+                ToolbarItem::Redaction(redaction) => redaction == self.style.redaction,
+                _ => false,
+            };
+            if selected {
+                TOOLBAR_HIGHLIGHT.set_source(ctx);
+                rounded_rect(ctx, rect, 4.0);
+                ctx.fill().unwrap();
+            }
+
+            TOOLBAR_ICON.set_source(ctx);
+            match item {
+                ToolbarItem::Tool(Tool::Text) => draw_label(ctx, "T", "Serif", 20.0, center),
+                ToolbarItem::Tool(Tool::Redact) | ToolbarItem::Redaction(Redaction::Pixelate) => {
+                    draw_checker(ctx, center)
+                }
+                ToolbarItem::Tool(Tool::Select) => {
+                    ctx.move_to(cx - 6.0, cy - 10.0);
+                    ctx.line_to(cx - 6.0, cy + 7.0);
+                    ctx.line_to(cx - 2.0, cy + 3.0);
+                    ctx.line_to(cx + 1.0, cy + 10.0);
+                    ctx.line_to(cx + 4.0, cy + 9.0);
+                    ctx.line_to(cx + 1.0, cy + 2.0);
+                    ctx.line_to(cx + 6.0, cy + 2.0);
+                    ctx.close_path();
+                    ctx.fill().unwrap();
+                }
+                ToolbarItem::Tool(Tool::Step) => {
+                    ctx.arc(cx, cy, 10.0, 0.0, 2.0 * PI);
+                    ctx.fill().unwrap();
+                    TOOLBAR_BACKGROUND.set_source(ctx);
+                    draw_label(ctx, "1", "Sans", 13.0, center);
+                }
+                ToolbarItem::Tool(Tool::Bubble) => {
+                    ctx.set_line_width(2.0);
+                    rounded_rect(ctx, (cx - 10.0, cy - 9.0, 20.0, 13.0), 4.0);
+                    ctx.move_to(cx - 5.0, cy + 4.0);
+                    ctx.line_to(cx - 7.0, cy + 10.0);
+                    ctx.line_to(cx + 1.0, cy + 4.0);
+                    ctx.stroke().unwrap();
+                }
+                ToolbarItem::Tool(tool @ (Tool::Pen | Tool::Highlighter)) => {
+                    // NOTE: 深色工具栏上正片叠底不可见，荧光笔图标用半透明的粗画笔表示
+                    let mut icon = Annotation::new(Tool::Pen, (bx + 8.0, by + 20.0), &icon_style);
+                    for (dx, dy) in [(4.0, -6.0), (8.0, -2.0), (12.0, 2.0), (16.0, -4.0)] {
+                        icon.drag_to((bx + 8.0 + dx, by + 20.0 + dy));
+                    }
+                    if tool == Tool::Highlighter {
+                        icon.width = 6.0;
+                        icon.color.a = 0.5;
+                    }
+                    icon.draw(ctx, None);
+                }
+                ToolbarItem::Tool(tool) => {
+                    let (start, end) = match tool {
+                        Tool::Line | Tool::Arrow => ((bx + 9.0, by + 23.0), (bx + 23.0, by + 9.0)),
+                        _ => ((bx + 8.0, by + 10.0), (bx + 24.0, by + 22.0)),
+                    };
+                    let mut icon = Annotation::new(tool, start, &icon_style);
+                    icon.drag_to(end);
+                    icon.draw(ctx, None);
+                }
+                ToolbarItem::Color(i) => {
+                    self.config.palette[i].set_source(ctx);
+                    ctx.arc(cx, cy, 9.0, 0.0, 2.0 * PI);
+                    ctx.fill_preserve().unwrap();
+                    TOOLBAR_ICON.set_source(ctx);
+                    ctx.set_line_width(1.0);
+                    ctx.stroke().unwrap();
+                }
+                ToolbarItem::Width(i) => {
+                    ctx.arc(
+                        cx,
+                        cy,
+                        (self.config.widths[i] / 2.0 + 1.0).min(12.0),
+                        0.0,
+                        2.0 * PI,
+                    );
+                    ctx.fill().unwrap();
+                }
+                ToolbarItem::Font(i) => draw_label(ctx, "Aa", &self.config.fonts[i], 14.0, center),
+                ToolbarItem::FontSize(i) => {
+                    let label = format!("{}", self.config.font_sizes[i]);
+                    draw_label(ctx, &label, "Sans", 11.0, center);
+                }
+                ToolbarItem::Redaction(Redaction::Blur) => {
+                    let gradient = cairo::RadialGradient::new(cx, cy, 2.0, cx, cy, 11.0);
+                    gradient.add_color_stop_rgba(0.0, 0.93, 0.93, 0.93, 1.0);
+                    gradient.add_color_stop_rgba(1.0, 0.93, 0.93, 0.93, 0.0);
+                    ctx.set_source(&gradient).unwrap();
+                    ctx.arc(cx, cy, 11.0, 0.0, 2.0 * PI);
+                    ctx.fill().unwrap();
+                }
+                ToolbarItem::Redaction(Redaction::Fill) => {
+                    ctx.rectangle(cx - 9.0, cy - 9.0, 18.0, 18.0);
+                    ctx.set_line_width(1.0);
+                    ctx.stroke_preserve().unwrap();
+                    ctx.set_source_rgb(0.0, 0.0, 0.0);
+                    ctx.fill().unwrap();
+                }
+                ToolbarItem::Cancel => {
+                    ctx.set_line_width(2.0);
+                    ctx.move_to(cx - 7.0, cy - 7.0);
+                    ctx.line_to(cx + 7.0, cy + 7.0);
+                    ctx.move_to(cx + 7.0, cy - 7.0);
+                    ctx.line_to(cx - 7.0, cy + 7.0);
+                    ctx.stroke().unwrap();
+                }
+                ToolbarItem::Save => {
+                    ctx.set_line_width(2.0);
+                    ctx.move_to(cx - 8.0, cy);
+                    ctx.line_to(cx - 3.0, cy + 6.0);
+                    ctx.line_to(cx + 8.0, cy - 6.0);
+                    ctx.stroke().unwrap();
+                }
+            }
+        }
+        ctx.restore().unwrap();
+    }
+}
+
+fn contains((x, y, w, h): (f64, f64, f64, f64), (px, py): (f64, f64)) -> bool {
+    px >= x && px < x + w && py >= y && py < y + h
+}
+
+/// 以 center 为中心绘制一行文字，使用当前颜色
+fn draw_label(ctx: &Context, text: &str, font: &str, size: f64, center: (f64, f64)) {
+    ctx.select_font_face(font, FontSlant::Normal, FontWeight::Normal);
+    ctx.set_font_size(size);
+    let extents = ctx.text_extents(text).unwrap();
+    ctx.move_to(
+        center.0 - extents.width() / 2.0 - extents.x_bearing(),
+        center.1 - extents.height() / 2.0 - extents.y_bearing(),
+    );
+    ctx.show_text(text).unwrap();
+}
+
+/// 选中标注的虚线外框和四角的控制柄
+fn draw_selection(ctx: &Context, (x, y, w, h): (f64, f64, f64, f64)) {
+    ctx.save().unwrap();
+    ctx.set_line_width(1.0);
+    ctx.set_dash(&[4.0, 4.0], 0.0);
+    TOOLBAR_ICON.set_source(ctx);
+    ctx.rectangle(x, y, w, h);
+    ctx.stroke().unwrap();
+    ctx.set_dash(&[], 0.0);
+    for (hx, hy) in [(x, y), (x + w, y), (x + w, y + h), (x, y + h)] {
+        ctx.rectangle(hx - HANDLE / 2.0, hy - HANDLE / 2.0, HANDLE, HANDLE);
+        TOOLBAR_ICON.set_source(ctx);
+        ctx.fill_preserve().unwrap();
+        TOOLBAR_BACKGROUND.set_source(ctx);
+        ctx.stroke().unwrap();
+    }
+    ctx.restore().unwrap();
+}
+
+/// 3x3 的棋盘格，表示马赛克
+fn draw_checker(ctx: &Context, (cx, cy): (f64, f64)) {
+    let cell = 6.0;
+    for row in 0..3 {
+        for col in 0..3 {
+            if (row + col) % 2 == 0 {
+                ctx.rectangle(
+                    cx - 9.0 + col as f64 * cell,
+                    cy - 9.0 + row as f64 * cell,
+                    cell,
+                    cell,
+                );
+            }
+        }
+    }
+    ctx.fill().unwrap();
+}
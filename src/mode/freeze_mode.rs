@@ -1,6 +1,8 @@
-use log::{debug, info};
+use cairo::ImageSurface;
+use log::{debug, error, info};
 use smithay_client_toolkit::shm::slot::Buffer;
-use wayland_client::protocol::wl_surface;
+use wayland_client::protocol::{wl_shm::Format, wl_surface};
+use wayland_protocols::wp::viewporter::client::wp_viewport;
 use wayland_protocols_wlr::{
     layer_shell::v1::client::{
         zwlr_layer_shell_v1::{self, Layer},
@@ -11,13 +13,24 @@ use wayland_protocols_wlr::{
 
 use crate::wayland_ctx::WaylandCtx;
 
+/// 冻结模式，每个输出各有一个 surface、layer 和 screencopy buffer，均按输出 id 索引
 #[derive(Default)]
 pub struct FreezeMode {
-    pub surface: Option<wl_surface::WlSurface>,
-    pub screencopy_frame: Option<zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1>,
-    pub layer_surface: Option<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
-    pub buffer: Option<Buffer>,
+    pub surfaces: Vec<wl_surface::WlSurface>,
+    pub screencopy_frames: Vec<zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1>,
+    pub layer_surfaces: Vec<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
+    pub buffers: Vec<Option<Buffer>>,
+    /// screencopy 帧是否上下颠倒
+    pub y_inverts: Vec<bool>,
+    /// screencopy buffer 的像素格式，由合成器选择
+    pub formats: Vec<Format>,
+    /// 将物理尺寸的 buffer 缩放到输出的逻辑尺寸
+    pub viewports: Vec<Option<wp_viewport::WpViewport>>,
     pub hide_cursor: bool,
+    /// 已收到 BufferDone 的帧数
+    pub buffer_done_count: usize,
+    /// 已收到 Ready 的帧数
+    pub ready_count: usize,
 }
 
 impl FreezeMode {
@@ -28,67 +41,229 @@ impl FreezeMode {
         }
     }
     pub fn before(&mut self, wl_ctx: &mut WaylandCtx) {
-        self.surface = Some(
-            wl_ctx
-                .compositor
-                .as_ref()
-                .unwrap()
-                .create_surface(wl_ctx.qh.as_mut().unwrap(), 1),
-        );
-        info!("create freeze_surface");
-
-        // NOTE: 发起屏幕copy请求
-        debug!("发起屏幕copy请求");
-        self.screencopy_frame = Some(wl_ctx.screencopy_manager.as_ref().unwrap().capture_output(
-            !self.hide_cursor as i32,
-            wl_ctx.output.as_ref().unwrap(),
-            &wl_ctx.qh.clone().unwrap(),
-            (),
-        ));
-        // 创建 layer
-        let layer = zwlr_layer_shell_v1::ZwlrLayerShellV1::get_layer_surface(
-            wl_ctx.layer_shell.as_ref().unwrap(),
-            self.surface.as_ref().unwrap(),
-            wl_ctx.output.as_ref(),
-            Layer::Overlay,
-            "foam_freeze".to_string(),
-            &wl_ctx.qh.clone().unwrap(),
-            1,
-        );
-        layer.set_anchor(Anchor::all());
-        layer.set_exclusive_zone(-1);
-        layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
-        self.layer_surface = Some(layer);
-
-        info!("create freeze_layer");
-        self.surface
-            .as_ref()
-            .unwrap()
-            .damage(0, 0, wl_ctx.width.unwrap(), wl_ctx.height.unwrap());
-        self.surface.as_ref().unwrap().commit();
+        // NOTE: 各输出的 BufferDone 可能分几次 dispatch 到达，主循环在此期间会重复调用
+        if !self.surfaces.is_empty() {
+            return;
+        }
+        let qh = wl_ctx.qh.clone().unwrap();
+        for output in wl_ctx.foam_outputs.iter() {
+            let surface = wl_ctx.compositor.as_ref().unwrap().create_surface(&qh, 1);
+            info!("create freeze_surface for output {}", output.id);
+
+            // NOTE: 发起屏幕copy请求
+            debug!("发起屏幕copy请求");
+            self.screencopy_frames.push(
+                wl_ctx.screencopy_manager.as_ref().unwrap().capture_output(
+                    !self.hide_cursor as i32,
+                    &output.wl_output,
+                    &qh,
+                    output.id,
+                ),
+            );
+            // 创建 layer
+            let layer = zwlr_layer_shell_v1::ZwlrLayerShellV1::get_layer_surface(
+                wl_ctx.layer_shell.as_ref().unwrap(),
+                &surface,
+                Some(&output.wl_output),
+                Layer::Overlay,
+                "foam_freeze".to_string(),
+                &qh,
+                1,
+            );
+            layer.set_anchor(Anchor::all());
+            layer.set_exclusive_zone(-1);
+            layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+            self.layer_surfaces.push(layer);
+
+            info!("create freeze_layer");
+            self.viewports
+                .push(wl_ctx.scale_surface(&surface, output.id));
+            surface.damage_buffer(0, 0, output.width, output.height);
+            surface.commit();
+            self.surfaces.push(surface);
+            self.buffers.push(None);
+            self.y_inverts.push(false);
+            self.formats.push(Format::Xrgb8888);
+        }
         debug!("after freeze before hook")
     }
 
     pub fn set_freeze(&mut self, wl_ctx: &mut WaylandCtx) {
-        self.buffer
-            .as_ref()
-            .unwrap()
-            .attach_to(self.surface.as_ref().unwrap())
-            .unwrap();
-        self.surface
-            .as_ref()
-            .unwrap()
-            .damage(0, 0, wl_ctx.width.unwrap(), wl_ctx.height.unwrap());
-        self.surface.as_ref().unwrap().commit();
+        for output in wl_ctx.foam_outputs.iter() {
+            let surface = &self.surfaces[output.id];
+            // NOTE: buffer 是输出 transform 作用后的帧，交给合成器还原
+            surface.set_buffer_transform(output.buffer_transform(self.y_inverts[output.id]));
+            self.buffers[output.id]
+                .as_ref()
+                .unwrap()
+                .attach_to(surface)
+                .unwrap();
+            surface.damage_buffer(0, 0, output.width, output.height);
+            surface.commit();
+        }
     }
 
     #[allow(unused)]
     pub fn unset_freeze(&mut self, wl_ctx: &mut WaylandCtx) {
-        self.surface.as_mut().unwrap().attach(None, 0, 0);
-        self.surface
-            .as_mut()
-            .unwrap()
-            .damage(0, 0, wl_ctx.width.unwrap(), wl_ctx.height.unwrap());
-        self.surface.as_mut().unwrap().commit();
+        for output in wl_ctx.foam_outputs.iter() {
+            let surface = &self.surfaces[output.id];
+            surface.attach(None, 0, 0);
+            surface.damage_buffer(0, 0, output.width, output.height);
+            surface.commit();
+        }
+    }
+
+    /// 读取全局逻辑坐标 pos 处的像素颜色，按 screencopy buffer 的像素格式解码
+    pub fn pixel(&mut self, wl_ctx: &mut WaylandCtx, pos: (f64, f64)) -> Option<(u8, u8, u8)> {
+        let output = wl_ctx
+            .foam_outputs
+            .iter()
+            .find(|o| o.intersect((pos.0, pos.1, 1.0, 1.0)).is_some())?;
+        let id = output.id;
+        let scale = output.buffer_scale();
+        let (width, height) = (output.width, output.height);
+        // NOTE: 取物理像素的中心，再由逻辑方向映射回 buffer 坐标
+        let physical = (
+            ((pos.0 - output.global_x as f64) * scale).floor() + 0.5,
+            ((pos.1 - output.global_y as f64) * scale).floor() + 0.5,
+        );
+        let matrix = output.buffer_matrix(self.y_inverts[id]).try_invert().ok()?;
+        let (bx, by) = matrix.transform_point(physical.0, physical.1);
+        let bx = (bx.floor() as i32).clamp(0, width - 1) as usize;
+        let by = (by.floor() as i32).clamp(0, height - 1) as usize;
+
+        let format = self.formats[id];
+        let buffer = self.buffers.get_mut(id)?.as_mut()?;
+        if let Err(e) = buffer.deactivate() {
+            debug!("关闭 buffer 出错：{}", e);
+        }
+        let stride = buffer.stride() as usize;
+        let canvas = buffer.canvas(wl_ctx.pool.as_mut()?)?;
+        let rgb = decode_pixel(format, &canvas[by * stride..], bx);
+        if rgb.is_none() {
+            error!("unsupported screencopy format {:?}", format);
+        }
+        rgb
+    }
+
+    /// 根据 surface 查找其所属的输出 id
+    pub fn output_of(&self, surface: &wl_surface::WlSurface) -> Option<usize> {
+        self.surfaces.iter().position(|s| s == surface)
+    }
+
+    /// 所有输出的 screencopy 帧是否都已收到 BufferDone
+    pub fn all_buffer_done(&self) -> bool {
+        self.buffer_done_count == self.screencopy_frames.len()
+    }
+
+    /// 所有输出的 screencopy 帧是否都已收到 Ready
+    pub fn all_ready(&self) -> bool {
+        self.ready_count == self.screencopy_frames.len()
+    }
+
+    /// 销毁所有 layer 和 surface
+    pub fn destroy(&mut self) {
+        for layer in self.layer_surfaces.drain(..) {
+            layer.destroy();
+        }
+        for surface in self.surfaces.drain(..) {
+            surface.destroy();
+        }
+    }
+}
+
+/// 解码一行中第 x 个像素，row 从该行开始。wl_shm 格式均为小端字节序
+fn decode_pixel(format: Format, row: &[u8], x: usize) -> Option<(u8, u8, u8)> {
+    let bytes = match format {
+        Format::Rgb888 | Format::Bgr888 => 3,
+        _ => 4,
+    };
+    let data = row.get(x * bytes..(x + 1) * bytes)?;
+    match format {
+        // NOTE: 0xAARRGGBB 在内存中为 B G R A
+        Format::Argb8888 | Format::Xrgb8888 => Some((data[2], data[1], data[0])),
+        Format::Abgr8888 | Format::Xbgr8888 => Some((data[0], data[1], data[2])),
+        Format::Rgb888 => Some((data[2], data[1], data[0])),
+        Format::Bgr888 => Some((data[0], data[1], data[2])),
+        Format::Argb2101010 | Format::Xrgb2101010 => {
+            let v = u32::from_le_bytes(data[..4].try_into().ok()?);
+            Some((ten_bit(v >> 20), ten_bit(v >> 10), ten_bit(v)))
+        }
+        Format::Abgr2101010 | Format::Xbgr2101010 => {
+            let v = u32::from_le_bytes(data[..4].try_into().ok()?);
+            Some((ten_bit(v), ten_bit(v >> 10), ten_bit(v >> 20)))
+        }
+        _ => None,
+    }
+}
+
+/// 将 screencopy buffer 按 format 解码为 cairo Rgb24 图片，格式不支持时返回 None。
+/// Argb8888 和 Xrgb8888 与 Rgb24 的内存布局相同，不需要转换
+pub fn to_rgb24(
+    format: Format,
+    data: &[u8],
+    width: i32,
+    height: i32,
+    stride: i32,
+) -> Option<ImageSurface> {
+    let mut surface = ImageSurface::create(cairo::Format::Rgb24, width, height).ok()?;
+    let out_stride = surface.stride() as usize;
+    let mut out = surface.data().ok()?;
+    for y in 0..height as usize {
+        let row = data.get(y * stride as usize..)?;
+        for x in 0..width as usize {
+            let (r, g, b) = decode_pixel(format, row, x)?;
+            // NOTE: cairo Rgb24 在小端机器上为 BGRX
+            let i = y * out_stride + x * 4;
+            out[i..i + 4].copy_from_slice(&[b, g, r, 0xff]);
+        }
+    }
+    drop(out);
+    Some(surface)
+}
+
+/// 10 位通道转换为 8 位
+fn ten_bit(v: u32) -> u8 {
+    (((v & 0x3ff) * 255 + 511) / 1023) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_formats() {
+        let bgra = [0x30, 0x20, 0x10, 0xff];
+        assert_eq!(
+            decode_pixel(Format::Xrgb8888, &bgra, 0),
+            Some((0x10, 0x20, 0x30))
+        );
+        assert_eq!(
+            decode_pixel(Format::Xbgr8888, &bgra, 0),
+            Some((0x30, 0x20, 0x10))
+        );
+        assert_eq!(
+            decode_pixel(Format::Bgr888, &[1, 2, 3, 4, 5, 6], 1),
+            Some((4, 5, 6))
+        );
+        // NOTE: r = 1023，g = 0，b = 512
+        let v: u32 = (1023 << 20) | 512;
+        assert_eq!(
+            decode_pixel(Format::Xrgb2101010, &v.to_le_bytes(), 0),
+            Some((255, 0, 128))
+        );
+        assert_eq!(decode_pixel(Format::Yuyv, &bgra, 0), None);
+    }
+
+    #[test]
+    fn convert_to_rgb24() {
+        // NOTE: 2x1 的 Xbgr8888，stride 含 4 字节填充
+        let data = [0x10, 0x20, 0x30, 0, 0x40, 0x50, 0x60, 0, 0, 0, 0, 0];
+        let mut surface = to_rgb24(Format::Xbgr8888, &data, 2, 1, 12).unwrap();
+        let out = surface.data().unwrap();
+        assert_eq!(out[..3], [0x30, 0x20, 0x10]);
+        assert_eq!(out[4..7], [0x60, 0x50, 0x40]);
+        drop(out);
+        assert!(to_rgb24(Format::Yuyv, &data, 2, 1, 12).is_none());
     }
 }
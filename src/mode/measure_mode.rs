@@ -0,0 +1,159 @@
+use cairo::{Context, FontSlant, FontWeight};
+use log::debug;
+use xkbcommon::xkb;
+
+use crate::config::Colors;
+
+/// 端点短线的半长，逻辑像素
+const TICK: f64 = 6.0;
+/// 标签与终点的距离
+const LABEL_OFFSET: f64 = 12.0;
+
+/// 一次测量，坐标为全局逻辑坐标
+#[derive(Debug, Clone, Copy)]
+pub struct Measurement {
+    pub start: (f64, f64),
+    pub end: (f64, f64),
+    /// 按住 Alt 拖动时测量矩形，否则测量线段
+    pub rect: bool,
+    /// 起点所在输出每个逻辑像素对应的物理像素数
+    pub scale: f64,
+}
+
+impl Measurement {
+    /// 标签的各行，同时给出逻辑像素和物理像素
+    fn label(&self) -> Vec<String> {
+        let (dx, dy) = (self.end.0 - self.start.0, self.end.1 - self.start.1);
+        let scale = self.scale;
+        if self.rect {
+            let (w, h) = (dx.abs(), dy.abs());
+            return vec![
+                format!("{} x {} px", w.round(), h.round()),
+                format!("{} x {} physical", (w * scale).round(), (h * scale).round()),
+            ];
+        }
+        let length = dx.hypot(dy);
+        // NOTE: 角度按 y 轴向上计算，水平向右为 0°，逆时针为正
+        let angle = (-dy).atan2(dx).to_degrees();
+        vec![
+            format!("{:.1} px  {:.1} physical", length, length * scale),
+            format!(
+                "dx {} ({})  dy {} ({})",
+                dx.round(),
+                (dx * scale).round(),
+                dy.round(),
+                (dy * scale).round()
+            ),
+            format!("{:.1}°", angle),
+        ]
+    }
+
+    fn draw(&self, ctx: &Context, colors: &Colors) {
+        let (x0, y0) = self.start;
+        let (x1, y1) = self.end;
+        ctx.save().unwrap();
+        ctx.new_path();
+        if self.rect {
+            ctx.rectangle(x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs());
+        } else {
+            ctx.move_to(x0, y0);
+            ctx.line_to(x1, y1);
+            // 两端垂直于线段的短线
+            let length = (x1 - x0).hypot(y1 - y0);
+            if length > 0.0 {
+                let (nx, ny) = (-(y1 - y0) / length * TICK, (x1 - x0) / length * TICK);
+                for (x, y) in [self.start, self.end] {
+                    ctx.move_to(x - nx, y - ny);
+                    ctx.line_to(x + nx, y + ny);
+                }
+            }
+        }
+        // NOTE: 白色描边垫底，深色和浅色背景上都能看清
+        ctx.set_source_rgb(1.0, 1.0, 1.0);
+        ctx.set_line_width(3.0);
+        ctx.stroke_preserve().unwrap();
+        colors.label.set_source(ctx);
+        ctx.set_line_width(1.0);
+        ctx.stroke().unwrap();
+
+        let lines = self.label();
+        ctx.select_font_face("Monospace", FontSlant::Normal, FontWeight::Normal);
+        ctx.set_font_size(13.0);
+        let (padding, line_height) = (4.0, 16.0);
+        let width = lines
+            .iter()
+            .map(|line| ctx.text_extents(line).unwrap().x_advance())
+            .fold(0.0, f64::max)
+            + padding * 2.0;
+        let (x, y) = (x1 + LABEL_OFFSET, y1 + LABEL_OFFSET);
+        ctx.rectangle(x, y, width, line_height * lines.len() as f64 + padding);
+        ctx.set_source_rgb(1.0, 1.0, 1.0);
+        ctx.fill().unwrap();
+        colors.label.set_source(ctx);
+        for (i, line) in lines.iter().enumerate() {
+            ctx.move_to(x + padding, y + (i + 1) as f64 * line_height - 2.0);
+            ctx.show_text(line).unwrap();
+        }
+        ctx.restore().unwrap();
+    }
+}
+
+/// 在冻结画面上测量距离和尺寸，测量结果保留到退出
+#[derive(Debug, Default)]
+pub struct MeasureMode {
+    pub measurements: Vec<Measurement>,
+    /// 正在拖动的测量
+    pub current: Option<Measurement>,
+    /// 需要重绘选择层
+    pub dirty: bool,
+}
+
+impl MeasureMode {
+    pub fn press(&mut self, pos: (f64, f64), rect: bool, scale: f64) {
+        self.current = Some(Measurement {
+            start: pos,
+            end: pos,
+            rect,
+            scale,
+        });
+        self.dirty = true;
+    }
+
+    pub fn motion(&mut self, pos: (f64, f64)) {
+        if let Some(current) = self.current.as_mut() {
+            current.end = pos;
+        }
+        // NOTE: 放大镜跟随鼠标，没有拖动时也要重绘
+        self.dirty = true;
+    }
+
+    /// 松开鼠标，长度为 0 的测量（单击）被丢弃
+    pub fn release(&mut self) {
+        if let Some(current) = self.current.take()
+            && current.start != current.end
+        {
+            debug!("measurement {:?}", current);
+            self.measurements.push(current);
+        }
+        self.dirty = true;
+    }
+
+    /// 处理按键，返回是否已处理。BackSpace、Delete 或 Ctrl+Z 删除最后一次测量
+    pub fn key(&mut self, keysym: xkb::Keysym, ctrl: bool) -> bool {
+        match keysym {
+            xkb::Keysym::BackSpace | xkb::Keysym::Delete => {}
+            xkb::Keysym::z if ctrl => {}
+            _ => return false,
+        }
+        self.measurements.pop();
+        self.dirty = true;
+        true
+    }
+
+    /// 在 ctx（全局逻辑坐标）中绘制所有测量
+    pub fn draw(&self, ctx: &Context, colors: &Colors) {
+        for measurement in self.measurements.iter().chain(self.current.iter()) {
+            measurement.draw(ctx, colors);
+        }
+    }
+}
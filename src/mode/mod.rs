@@ -1,6 +1,9 @@
 use crate::wayland_ctx;
 
+pub mod adjust_mode;
+pub mod edit_mode;
 pub mod freeze_mode;
+pub mod measure_mode;
 pub mod result_mode;
 pub mod select_mode;
 
@@ -11,8 +14,14 @@ pub enum Mode {
     PreSelect,
     Await,
     OnDraw,
+    /// 松开鼠标后调整选区，确认后截图或进入编辑
+    Adjust,
+    /// foamshot measure 中已开始测量
+    Measure,
     ShowResult,
     Output,
+    /// 界面已关闭，继续提供剪贴板内容直到被其他程序接管
+    ServeClipboard,
     Exit,
 }
 
@@ -1,24 +1,46 @@
 use std::io::Write;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 
 use cairo::{Context, ImageSurface};
-use log::{debug, error};
+use log::{debug, error, info};
 use smithay_client_toolkit::shm::slot::Buffer;
-use wayland_protocols_wlr::screencopy::v1::client::zwlr_screencopy_frame_v1;
+use wayland_client::protocol::wl_shm::Format;
 
-use super::freeze_mode::FreezeMode;
+use super::freeze_mode::{FreezeMode, to_rgb24};
+use crate::annotation::Annotation;
+use crate::clipboard::Clipboard;
 use crate::config::Cli;
+use crate::path_template::TemplateInfo;
+use crate::project::Project;
+use crate::redact::Backdrop;
 use crate::wayland_ctx::WaylandCtx;
 
 #[derive(Default)]
 pub struct ResultMode {
     pub quickshot: bool,
     pub full_screen: bool,
+    /// 全屏截图的目标输出，未指定时使用鼠标所在的输出
+    pub target_output: Option<usize>,
     pub buffer: Option<Buffer>,
     pub start: Option<(i32, i32)>,
     pub width: Option<i32>,
     pub height: Option<i32>,
-    pub screencopy_frame: Option<zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1>,
+    /// 保存的文件路径，输出到 stdout 时为 None
+    pub path: Option<PathBuf>,
+    /// 已设置到剪贴板的截图，退出界面后继续提供
+    pub clipboard: Option<Clipboard>,
+    /// 从工程文件打开的截图，设置后不再从冻结画面截取
+    pub source: Option<Source>,
+}
+
+/// 从工程文件打开的原始截图
+pub struct Source {
+    pub image: ImageSurface,
+    /// 在屏幕上显示的区域，全局逻辑坐标
+    pub region: (f64, f64, f64, f64),
+    /// region 相对于工程尺寸的缩放比例
+    pub fit: f64,
 }
 
 impl ResultMode {
@@ -30,17 +52,28 @@ impl ResultMode {
         }
     }
 
-    /// 计算截图区域。
-    /// 如果 full_screen 为 true 则返回全屏区域，否则根据 WaylandCtx 中的 start_pos 和 end_pos 计算区域。
-    fn calculate_region(&self, wl_ctx: &WaylandCtx) -> Option<(f64, f64, f64, f64)> {
+    /// 计算截图区域（全局逻辑坐标）。
+    /// full_screen 为 true 时返回目标输出或鼠标所在输出的区域，都没有时（如 --full-screen）返回所有输出的外接矩形；
+    /// 否则根据 WaylandCtx 中的 start_pos 和 end_pos 计算区域。
+    pub fn calculate_region(&self, wl_ctx: &WaylandCtx) -> Option<(f64, f64, f64, f64)> {
+        if let Some(source) = &self.source {
+            return Some(source.region);
+        }
+        let bounds = wl_ctx.layout_bounds()?;
         if self.full_screen {
-            // 全屏模式下，直接使用屏幕的宽高
-            let (w, h) = (wl_ctx.width?, wl_ctx.height?);
-            Some((0.0, 0.0, w as f64, h as f64))
+            match self.target_output.or(wl_ctx.pointer_output) {
+                Some(id) => Some(wl_ctx.foam_outputs[id].global_rect()),
+                None => Some(bounds),
+            }
         } else {
             // 非全屏模式，需要通过起始和结束坐标计算区域
             let (start_x, start_y) = wl_ctx.start_pos?;
             let (end_x, end_y) = wl_ctx.end_pos?;
+            // 拖动可能越过桌面边界，限制在所有输出的范围内
+            let (bx, by, bw, bh) = bounds;
+            let clamp = |(x, y): (f64, f64)| (x.clamp(bx, bx + bw), y.clamp(by, by + bh));
+            let (start_x, start_y) = clamp((start_x, start_y));
+            let (end_x, end_y) = clamp((end_x, end_y));
             let x = start_x.min(end_x);
             let y = start_y.min(end_y);
             let mut width = (end_x - start_x).abs();
@@ -56,7 +89,125 @@ impl ResultMode {
         }
     }
 
-    pub fn to_png_2(&mut self, cli: &Cli, wl_ctx: &mut WaylandCtx, freeze_frame: &mut FreezeMode) {
+    /// 区域所在的输出名称，取相交面积最大的输出；全屏截取所有输出时为 all
+    fn output_name(&self, wl_ctx: &WaylandCtx, region: (f64, f64, f64, f64)) -> String {
+        if self.full_screen && self.target_output.or(wl_ctx.pointer_output).is_none() {
+            return "all".to_string();
+        }
+        wl_ctx
+            .foam_outputs
+            .iter()
+            .filter_map(|o| o.intersect(region).map(|(_, _, w, h)| (o, w * h)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .and_then(|(o, _)| o.name.clone())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// 将各输出的冻结画面中落在区域内的部分拼接为一张图片。
+    /// 输出图片使用相交输出中最大的缩放比，缩放比较小的输出会被放大。
+    pub fn composite(
+        &self,
+        wl_ctx: &mut WaylandCtx,
+        freeze_frame: &mut FreezeMode,
+        region: (f64, f64, f64, f64),
+    ) -> Option<ImageSurface> {
+        // NOTE: 打开工程时区域总是 source 的区域
+        if let Some(source) = &self.source {
+            return copy_surface(&source.image);
+        }
+        let (x, y, width, height) = region;
+        let ids: Vec<usize> = wl_ctx
+            .foam_outputs
+            .iter()
+            .filter(|o| o.intersect(region).is_some())
+            .map(|o| o.id)
+            .collect();
+        if ids.is_empty() {
+            error!("截图区域不在任何输出内");
+            return None;
+        }
+        let scale = ids
+            .iter()
+            .map(|&id| wl_ctx.foam_outputs[id].buffer_scale())
+            .fold(1.0, f64::max);
+
+        // 为截取区域创建新的 Cairo ImageSurface
+        let cropped_surface = ImageSurface::create(
+            cairo::Format::Rgb24,
+            (width * scale).round() as i32,
+            (height * scale).round() as i32,
+        )
+        .expect("无法创建截取区域的 surface");
+        let cr = Context::new(&cropped_surface).expect("创建 Cairo 画布失败");
+
+        for id in ids {
+            let Some(Some(buffer)) = freeze_frame.buffers.get_mut(id) else {
+                error!("freeze_frame 中未找到输出 {} 的 buffer", id);
+                return None;
+            };
+            if let Err(e) = buffer.deactivate() {
+                debug!("关闭 buffer 出错：{}", e);
+            }
+
+            let output = &wl_ctx.foam_outputs[id];
+            let (full_width, full_height) = (output.width, output.height);
+            let (global_x, global_y) = (output.global_x as f64, output.global_y as f64);
+            let output_scale = output.buffer_scale();
+            let buffer_matrix = output.buffer_matrix(freeze_frame.y_inverts[id]);
+
+            // 从 WaylandCtx 的共享内存中获取 canvas
+            let pool = wl_ctx.pool.as_mut().expect("WaylandCtx 中缺少 pool");
+            let stride = buffer.stride();
+            let canvas = buffer.canvas(pool).expect("获取 canvas 失败");
+
+            // 为整个画面创建 Cairo ImageSurface，其他像素格式先按格式转换
+            let format = freeze_frame.formats[id];
+            let cairo_surface = if matches!(format, Format::Argb8888 | Format::Xrgb8888) {
+                unsafe {
+                    ImageSurface::create_for_data_unsafe(
+                        canvas.as_mut_ptr(),
+                        cairo::Format::Rgb24,
+                        full_width,
+                        full_height,
+                        stride,
+                    )
+                    .expect("创建 Cairo ImageSurface 失败")
+                }
+            } else {
+                match to_rgb24(format, canvas, full_width, full_height, stride) {
+                    Some(surface) => surface,
+                    None => {
+                        error!("unsupported screencopy format {:?}", format);
+                        return None;
+                    }
+                }
+            };
+
+            // 将输出的 buffer 坐标映射到截图区域坐标
+            cr.save().unwrap();
+            cr.scale(scale, scale);
+            cr.translate(global_x - x, global_y - y);
+            cr.scale(1.0 / output_scale, 1.0 / output_scale);
+            // 将 buffer 还原为逻辑方向
+            cr.transform(buffer_matrix);
+            cr.set_source_surface(&cairo_surface, 0.0, 0.0)
+                .expect("设置绘制区域失败");
+            cr.rectangle(0.0, 0.0, full_width as f64, full_height as f64);
+            cr.fill().expect("绘制截取区域失败");
+            cr.restore().unwrap();
+        }
+        drop(cr);
+
+        Some(cropped_surface)
+    }
+
+    pub fn to_png_2(
+        &mut self,
+        cli: &Cli,
+        wl_ctx: &mut WaylandCtx,
+        freeze_frame: &mut FreezeMode,
+        annotations: &[Annotation],
+    ) {
         // 根据配置计算截图区域
         let (x, y, width, height) = match self.calculate_region(wl_ctx) {
             Some(region) => region,
@@ -66,15 +217,6 @@ impl ResultMode {
             }
         };
 
-        if let Some(buffer) = freeze_frame.buffer.as_mut() {
-            if let Err(e) = buffer.deactivate() {
-                debug!("关闭 buffer 出错：{}", e);
-            }
-        } else {
-            error!("freeze_frame 中未找到 buffer");
-            return;
-        }
-
         debug!(
             "截图区域 - x: {}, y: {}, width: {}, height: {}",
             x as i32, y as i32, width as i32, height as i32
@@ -83,72 +225,129 @@ impl ResultMode {
         self.width = Some(width as i32);
         self.height = Some(height as i32);
 
-        // 从 WaylandCtx 的共享内存中获取 canvas
-        let pool = wl_ctx.pool.as_mut().expect("WaylandCtx 中缺少 pool");
-        let canvas = freeze_frame
-            .buffer
-            .as_ref()
-            .unwrap()
-            .canvas(pool)
-            .expect("获取 canvas 失败");
-
-        let full_width = wl_ctx.width.expect("WaylandCtx 缺少屏幕宽度");
-        let full_height = wl_ctx.height.expect("WaylandCtx 缺少屏幕高度");
-
-        // 为整个画面创建 Cairo ImageSurface
-        let cairo_surface = unsafe {
-            ImageSurface::create_for_data_unsafe(
-                canvas.as_mut_ptr(),
-                cairo::Format::Rgb24,
-                full_width,
-                full_height,
-                full_width * 4,
-            )
-            .expect("创建 Cairo ImageSurface 失败")
+        let Some(cropped_surface) = self.composite(wl_ctx, freeze_frame, (x, y, width, height))
+        else {
+            return;
         };
 
-        // 为截取区域创建新的 Cairo ImageSurface
-        let cropped_surface =
-            ImageSurface::create(cairo::Format::Rgb24, width as i32, height as i32)
-                .expect("无法创建截取区域的 surface");
+        // 标注在保存时才绘制到截图上，打码从未绘制标注的副本取样，工程文件也保存该副本
+        let base = if !annotations.is_empty() || cli.project {
+            self.composite(wl_ctx, freeze_frame, (x, y, width, height))
+        } else {
+            None
+        };
+        if let Some(base) = base.as_ref()
+            && !annotations.is_empty()
+        {
+            let backdrop = Backdrop {
+                image: base,
+                origin: (x, y),
+                scale: base.width() as f64 / width,
+            };
+            let cr = Context::new(&cropped_surface).expect("创建 Cairo 画布失败");
+            cr.scale(
+                cropped_surface.width() as f64 / width,
+                cropped_surface.height() as f64 / height,
+            );
+            cr.translate(-x, -y);
+            for annotation in annotations {
+                annotation.draw(&cr, Some(&backdrop));
+            }
+        }
 
-        // 使用新的 Context 将指定区域绘制到 cropped_surface 上
-        let cr = Context::new(&cropped_surface).expect("创建 Cairo 画布失败");
-        cr.set_source_surface(&cairo_surface, -x, -y)
-            .expect("设置绘制区域失败");
-        cr.paint().expect("绘制截取区域失败");
-
-        // 将裁剪后的图像写入 PNG 文件
-        let file = std::fs::File::create(&cli.output_path).expect("无法创建输出文件");
-        let mut buffer_writer = std::io::BufWriter::new(file);
-        cropped_surface
-            .write_to_png(&mut buffer_writer)
-            .expect("写入 PNG 失败");
-        buffer_writer.flush().expect("刷新文件失败");
+        // 按输出格式编码，剪贴板使用同一份数据
+        let encoded = match cli.format.encode(&cropped_surface, cli.quality) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        };
+
+        // 将编码后的图像写入文件或 stdout
+        if cli.to_stdout() {
+            let mut stdout = std::io::stdout().lock();
+            if let Err(e) = stdout.write_all(&encoded).and_then(|_| stdout.flush()) {
+                error!("failed to write image to stdout: {}", e);
+                return;
+            }
+        } else {
+            let output = self.output_name(wl_ctx, (x, y, width, height));
+            // NOTE: 打开工程时区域按屏幕缩小过，文件名和工程使用工程本身的尺寸
+            let fit = self.source.as_ref().map_or(1.0, |source| source.fit);
+            let info = TemplateInfo {
+                output: &output,
+                width: (width / fit).round() as i32,
+                height: (height / fit).round() as i32,
+                full_screen: self.full_screen,
+            };
+            let (path, file) = match cli.output_path.create(&info) {
+                Ok(created) => created,
+                Err(e) => {
+                    error!("failed to create output file: {}", e);
+                    return;
+                }
+            };
+            info!("output path: {}", path.display());
+            let mut buffer_writer = std::io::BufWriter::new(file);
+            buffer_writer.write_all(&encoded).expect("写入图片失败");
+            buffer_writer.flush().expect("刷新文件失败");
+            if cli.project
+                && let Some(base) = base.as_ref()
+                && let Err(e) = Project::save(&path, base, (x, y, width, height), fit, annotations)
+            {
+                error!("{}", e);
+            }
+            self.path = Some(path);
+        }
+
+        self.run_hooks(cli);
 
         // 如果 auto_copy 选项开启，则复制图片到剪贴板
         if cli.auto_copy {
-            let mut png_data = Vec::new();
-            cropped_surface
-                .write_to_png(&mut png_data)
-                .expect("无法写入 PNG 数据到内存");
-
-            let mut process = Command::new("wl-copy")
-                .arg("--type")
-                .arg("image/png")
-                .stdin(Stdio::piped())
-                .spawn()
-                .expect("启动 wl-copy 进程失败");
-
-            if let Some(stdin) = process.stdin.as_mut() {
-                stdin.write_all(&png_data).expect("写入剪贴板数据失败");
-            } else {
-                error!("无法获取 wl-copy 的标准输入");
+            let clipboard = Clipboard::new(
+                encoded,
+                cli.format,
+                cropped_surface,
+                self.path.as_deref(),
+                cli.quality,
+            );
+            if clipboard.offer(wl_ctx) {
+                self.clipboard = Some(clipboard);
             }
-
-            process.wait().expect("等待 wl-copy 进程结束失败");
         }
 
         // std::process::exit(0);
     }
+
+    /// 执行保存后的 hook 命令，不等待其结束。输出到 stdout 时不设置 FOAMSHOT_PATH
+    fn run_hooks(&self, cli: &Cli) {
+        for hook in cli.hooks.iter() {
+            debug!("run hook: {}", hook);
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(hook);
+            match &self.path {
+                Some(path) => {
+                    command.env("FOAMSHOT_PATH", path);
+                }
+                None => {
+                    // NOTE: hook 的输出不能混入 stdout 的图片数据
+                    command.stdout(Stdio::null());
+                }
+            }
+            if let Err(e) = command.spawn() {
+                error!("failed to run hook `{}`: {}", hook, e);
+            }
+        }
+    }
+}
+
+/// 复制一份 Rgb24 图片
+fn copy_surface(image: &ImageSurface) -> Option<ImageSurface> {
+    let copy = ImageSurface::create(cairo::Format::Rgb24, image.width(), image.height()).ok()?;
+    let cr = Context::new(&copy).ok()?;
+    cr.set_source_surface(image, 0.0, 0.0).ok()?;
+    cr.paint().ok()?;
+    drop(cr);
+    Some(copy)
 }
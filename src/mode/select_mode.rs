@@ -2,6 +2,9 @@ use cairo::{Context, FontSlant, FontWeight, ImageSurface};
 use log::{debug, info};
 use smithay_client_toolkit::shm::slot::Buffer;
 use wayland_client::protocol::{wl_shm::Format, wl_surface};
+use wayland_protocols::wp::{
+    fractional_scale::v1::client::wp_fractional_scale_v1, viewporter::client::wp_viewport,
+};
 use wayland_protocols_wlr::{
     layer_shell::v1::client::{
         zwlr_layer_shell_v1::{self, Layer},
@@ -10,171 +13,394 @@ use wayland_protocols_wlr::{
     // screencopy::v1::client::zwlr_screencopy_frame_v1,
 };
 
+use super::adjust_mode::AdjustMode;
+use super::edit_mode::EditMode;
+use super::measure_mode::MeasureMode;
+use crate::config::{Color, Colors};
+use crate::loupe::Loupe;
 use crate::wayland_ctx::WaylandCtx;
 
+/// 按下和松开的距离小于该值时视为单击，逻辑像素
+const CLICK_DISTANCE: f64 = 3.0;
+
 #[derive(Default)]
 // #[allow(unused)]
 pub struct SelectMode {
-    pub surface: Option<wl_surface::WlSurface>,
+    /// 每个输出的选择层 surface，按输出 id 索引
+    pub surfaces: Vec<wl_surface::WlSurface>,
     // pub screencopy_frame: Option<zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1>,
-    pub layer_surface: Option<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
-    pub buffer: Option<Buffer>,
+    pub layer_surfaces: Vec<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
+    pub buffers: Vec<Option<Buffer>>,
+    pub viewports: Vec<Option<wp_viewport::WpViewport>>,
+    pub fractional_scales: Vec<wp_fractional_scale_v1::WpFractionalScaleV1>,
     pub last_pos: (f64, f64),
+    /// 吸附后的框选起点，全局逻辑坐标
+    pub start: (f64, f64),
+    /// 修饰键变化后需要重绘选区
+    pub dirty: bool,
+    /// 上一次绘制的放大镜所在的矩形，移动后需要清除
+    pub last_loupe_rect: Option<(f64, f64, f64, f64)>,
+    /// 合成器提供的可见窗口，全局逻辑坐标，越靠前越在上层
+    pub windows: Vec<(f64, f64, f64, f64)>,
+    /// 开始框选前鼠标下的窗口
+    pub hover: Option<(f64, f64, f64, f64)>,
+    pub colors: Colors,
 }
 impl SelectMode {
+    pub fn new(colors: Colors) -> Self {
+        Self {
+            colors,
+            ..Default::default()
+        }
+    }
+
     #[inline]
     pub fn before(&mut self, wl_ctx: &mut WaylandCtx) {
-        self.surface = Some(
-            wl_ctx
-                .compositor
-                .as_ref()
-                .unwrap()
-                .create_surface(wl_ctx.qh.as_mut().unwrap(), 2),
-        );
-        self.surface.as_ref().unwrap().commit();
-
-        let layer = zwlr_layer_shell_v1::ZwlrLayerShellV1::get_layer_surface(
-            wl_ctx.layer_shell.as_ref().unwrap(),
-            self.surface.as_ref().unwrap(),
-            wl_ctx.output.as_ref(),
-            Layer::Overlay,
-            "foam_select".to_string(),
-            &wl_ctx.qh.clone().unwrap(),
-            2,
-        );
-        layer.set_anchor(Anchor::all());
-        layer.set_exclusive_zone(-1);
-        layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
-        self.layer_surface = Some(layer);
-
-        info!("create select_layer");
-        self.surface
-            .as_ref()
-            .unwrap()
-            .damage(0, 0, wl_ctx.width.unwrap(), wl_ctx.height.unwrap());
-        self.surface.as_ref().unwrap().commit();
+        // NOTE: 主循环在等待所有帧 Ready 期间会重复调用
+        if !self.surfaces.is_empty() {
+            return;
+        }
+        let qh = wl_ctx.qh.clone().unwrap();
+        for output in wl_ctx.foam_outputs.iter() {
+            let surface = wl_ctx.compositor.as_ref().unwrap().create_surface(&qh, 2);
+            surface.commit();
+
+            let layer = zwlr_layer_shell_v1::ZwlrLayerShellV1::get_layer_surface(
+                wl_ctx.layer_shell.as_ref().unwrap(),
+                &surface,
+                Some(&output.wl_output),
+                Layer::Overlay,
+                "foam_select".to_string(),
+                &qh,
+                2,
+            );
+            layer.set_anchor(Anchor::all());
+            layer.set_exclusive_zone(-1);
+            layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+            self.layer_surfaces.push(layer);
+
+            info!("create select_layer for output {}", output.id);
+            self.viewports
+                .push(wl_ctx.scale_surface(&surface, output.id));
+            if let Some(manager) = wl_ctx.fractional_scale_manager.as_ref() {
+                self.fractional_scales
+                    .push(manager.get_fractional_scale(&surface, &qh, output.id));
+            }
+            surface.commit();
+            self.surfaces.push(surface);
+            self.buffers.push(None);
+        }
     }
 
     pub fn on(&mut self, wl_ctx: &mut WaylandCtx) {
-        match wl_ctx
-            .create_buffer(
-                wl_ctx.width.unwrap(),
-                wl_ctx.height.unwrap(),
-                wl_ctx.width.unwrap() * 4,
-                Format::Argb8888,
-            )
-            .ok()
-        {
-            Some((buffer, canvas)) => {
-                // self.buffer = Some(buffer);
-                canvas.fill(80);
-                self.buffer = Some(buffer);
-
-                self.buffer
-                    .as_mut()
-                    .unwrap()
-                    .attach_to(self.surface.as_mut().unwrap())
-                    .unwrap();
-                // self.buffer = Some(buffer);
-                debug!("请求重绘");
-                self.surface.as_ref().unwrap().damage(
-                    0,
-                    0,
-                    wl_ctx.width.unwrap(),
-                    wl_ctx.height.unwrap(),
-                );
-                self.surface.as_ref().unwrap().commit();
-                debug!("wait for select");
-            }
-            None => {
-                std::process::exit(0);
+        // NOTE: 遮罩已重置，悬停的窗口和放大镜需要重新绘制
+        self.hover = None;
+        self.last_loupe_rect = None;
+        for id in 0..wl_ctx.foam_outputs.len() {
+            let (width, height) = wl_ctx.overlay_buffer_size(id);
+            match wl_ctx
+                .create_buffer(width, height, width * 4, Format::Argb8888)
+                .ok()
+            {
+                Some((buffer, canvas)) => {
+                    // NOTE: ARGB8888 为预乘 alpha，小端字节序为 BGRA
+                    let Color { r, g, b, a } = self.colors.overlay;
+                    let pixel = [b * a, g * a, r * a, a].map(|c| (c * 255.0).round() as u8);
+                    for chunk in canvas.chunks_exact_mut(4) {
+                        chunk.copy_from_slice(&pixel);
+                    }
+                    buffer.attach_to(&self.surfaces[id]).unwrap();
+                    self.buffers[id] = Some(buffer);
+                    debug!("请求重绘");
+                    self.surfaces[id].damage_buffer(0, 0, width, height);
+                    self.surfaces[id].commit();
+                }
+                None => {
+                    std::process::exit(0);
+                }
             }
         }
+        debug!("wait for select");
+    }
+
+    /// 根据 surface 查找其所属的输出 id
+    pub fn output_of(&self, surface: &wl_surface::WlSurface) -> Option<usize> {
+        self.surfaces.iter().position(|s| s == surface)
     }
-    pub fn after(&mut self, wl_ctx: &mut WaylandCtx) {
-        if let (Some(width), Some(height), Some((start_x, start_y)), Some((end_x, end_y))) = (
-            wl_ctx.width,
-            wl_ctx.height,
-            wl_ctx.start_pos,
-            wl_ctx.current_pos,
-        ) {
-            self.last_pos = (end_x, end_y);
+
+    /// 绘制从 start 到 end（吸附后的鼠标位置）的选区
+    pub fn after(&mut self, wl_ctx: &mut WaylandCtx, end: (f64, f64), loupe: Option<&Loupe>) {
+        if let Some(pos) = wl_ctx.current_pos {
+            let start = self.start;
+            self.last_pos = pos;
+            self.last_loupe_rect = loupe.map(Loupe::rect);
 
             debug!("update select");
-            let (buffer, canvas) = wl_ctx
-                .create_buffer(width, height, width * 4, Format::Argb8888)
-                .unwrap();
-            canvas.fill(0);
-
-            let cairo_surface = unsafe {
-                ImageSurface::create_for_data(
-                    std::slice::from_raw_parts_mut(canvas.as_mut_ptr(), canvas.len()),
-                    cairo::Format::ARgb32,
-                    width,
-                    height,
-                    width * 4,
-                )
-                .map_err(|e| format!("Failed to create Cairo surface: {}", e))
-                .unwrap()
-            };
-
-            // 创建 Cairo 上下文
-            let ctx = Context::new(&cairo_surface)
-                .map_err(|e| format!("Failed to create Cairo context: {}", e))
-                .unwrap();
-
-            // 填充整个表面为白色半透明
-            ctx.set_source_rgba(1.0, 1.0, 1.0, 0.3);
-            ctx.paint().unwrap();
-
-            let width = end_x - start_x;
-            let height = end_y - start_y;
-            // 清除矩形区域以显示透明
-            ctx.set_operator(cairo::Operator::Clear);
-            ctx.rectangle(start_x, start_y, width, height);
-            ctx.fill().unwrap();
-
-            // 确保宽度和高度为非负整数
-            let width = (end_x - start_x).abs() as i32;
-            let height = (end_y - start_y).abs() as i32;
-
-            // 创建文本内容
-            let text = format!("{}x{}", width, height);
-
-            // 计算文本位置，使其位于矩形的右下角外侧
-            let text_extent = ctx.text_extents(&text).unwrap();
-            let _text_width = text_extent.width();
-            let text_height = text_extent.height();
-
-            // 确定矩形的右下角坐标
-            let rect_end_x = f64::max(start_x, end_x);
-            let rect_end_y = f64::max(start_y, end_y);
-
-            // 文本位置在矩形的右下角外侧，沿对角线延伸
-            let text_x = rect_end_x + 10.0; // 向右偏移10像素
-            let text_y = rect_end_y + text_height + 10.0; // 向下偏移10像素加上文本高度
-
-            // 设置文本颜色为黑色
-            ctx.set_source_rgb(0.0, 0.0, 0.0);
-            ctx.select_font_face("Sans", FontSlant::Normal, FontWeight::Normal);
-            ctx.set_font_size(16.0);
-
-            // 绘制文本
-            ctx.move_to(text_x, text_y);
-            ctx.show_text(&text).unwrap();
-
-            cairo_surface.flush();
-
-            buffer.attach_to(self.surface.as_ref().unwrap()).unwrap();
-            self.buffer = Some(buffer);
-            // 请求重绘
-            self.surface.as_ref().unwrap().damage_buffer(
-                0,
-                0,
-                wl_ctx.width.unwrap(),
-                wl_ctx.height.unwrap(),
-            );
-            self.surface.as_ref().unwrap().commit();
+            // NOTE: 选区可能跨越多个输出，每个输出都需要重绘
+            for id in 0..wl_ctx.foam_outputs.len() {
+                self.draw_output(wl_ctx, id, start, end, loupe);
+            }
+        }
+    }
+
+    /// 在指定输出的选择层上绘制选区，start 和 end 为全局坐标
+    fn draw_output(
+        &mut self,
+        wl_ctx: &mut WaylandCtx,
+        id: usize,
+        start: (f64, f64),
+        end: (f64, f64),
+        loupe: Option<&Loupe>,
+    ) {
+        let colors = self.colors.clone();
+        self.render(wl_ctx, id, |ctx| {
+            draw_selection(ctx, &colors, start, end);
+            if let Some(loupe) = loupe {
+                loupe.draw(ctx);
+            }
+        });
+    }
+
+    /// pos 处最上层的窗口
+    pub fn window_at(&self, pos: (f64, f64)) -> Option<(f64, f64, f64, f64)> {
+        self.windows
+            .iter()
+            .copied()
+            .find(|&(x, y, w, h)| pos.0 >= x && pos.0 < x + w && pos.1 >= y && pos.1 < y + h)
+    }
+
+    /// 按下和松开的位置足够近时视为单击，返回单击的窗口
+    pub fn clicked_window(
+        &self,
+        start: (f64, f64),
+        end: (f64, f64),
+    ) -> Option<(f64, f64, f64, f64)> {
+        let distance = (end.0 - start.0).hypot(end.1 - start.1);
+        (distance < CLICK_DISTANCE)
+            .then(|| self.window_at(end))
+            .flatten()
+    }
+
+    /// 开始框选前高亮鼠标下的窗口并绘制放大镜。
+    /// 悬停的窗口变化时重绘所有输出，否则只重绘放大镜现在和上一次所在的输出
+    pub fn draw_await(
+        &mut self,
+        wl_ctx: &mut WaylandCtx,
+        hover: Option<(f64, f64, f64, f64)>,
+        loupe: Option<&Loupe>,
+    ) {
+        let rect = loupe.map(Loupe::rect);
+        let last = std::mem::replace(&mut self.last_loupe_rect, rect);
+        let redraw_all = hover != self.hover;
+        self.hover = hover;
+        let colors = self.colors.clone();
+        for id in 0..wl_ctx.foam_outputs.len() {
+            let output = &wl_ctx.foam_outputs[id];
+            if !redraw_all
+                && [rect, last]
+                    .into_iter()
+                    .flatten()
+                    .all(|r| output.intersect(r).is_none())
+            {
+                continue;
+            }
+            self.render(wl_ctx, id, |ctx| {
+                match hover {
+                    Some((x, y, w, h)) => draw_selection(ctx, &colors, (x, y), (x + w, y + h)),
+                    None => draw_mask(ctx, &colors, (0.0, 0.0, 0.0, 0.0)),
+                }
+                if let Some(loupe) = loupe {
+                    loupe.draw(ctx);
+                }
+            });
+        }
+    }
+
+    /// 在所有输出的选择层上绘制调整中的选区和控制柄
+    pub fn draw_adjust(&mut self, wl_ctx: &mut WaylandCtx, adjust: &AdjustMode) {
+        let colors = self.colors.clone();
+        let (x, y, w, h) = adjust.region;
+        for id in 0..wl_ctx.foam_outputs.len() {
+            self.render(wl_ctx, id, |ctx| {
+                draw_selection(ctx, &colors, (x, y), (x + w, y + h));
+                draw_handles(ctx, &colors, adjust);
+                if let Some(input) = adjust.input.as_deref() {
+                    draw_input(ctx, &colors, input, (x, y));
+                }
+            });
         }
     }
+
+    /// 在所有输出的选择层上绘制测量结果和放大镜
+    pub fn draw_measure(
+        &mut self,
+        wl_ctx: &mut WaylandCtx,
+        measure: &MeasureMode,
+        loupe: Option<&Loupe>,
+    ) {
+        let colors = self.colors.clone();
+        self.last_loupe_rect = loupe.map(Loupe::rect);
+        for id in 0..wl_ctx.foam_outputs.len() {
+            self.render(wl_ctx, id, |ctx| {
+                draw_mask(ctx, &colors, (0.0, 0.0, 0.0, 0.0));
+                measure.draw(ctx, &colors);
+                if let Some(loupe) = loupe {
+                    loupe.draw(ctx);
+                }
+            });
+        }
+    }
+
+    /// 在所有输出的选择层上绘制编辑中的选区、标注和工具栏
+    pub fn draw_edit(&mut self, wl_ctx: &mut WaylandCtx, edit: &EditMode) {
+        let colors = self.colors.clone();
+        for id in 0..wl_ctx.foam_outputs.len() {
+            self.render(wl_ctx, id, |ctx| {
+                draw_mask(ctx, &colors, edit.region);
+                edit.draw(ctx);
+            });
+        }
+    }
+
+    /// 为指定输出创建新的 buffer 并绘制，draw 在全局逻辑坐标中绘制
+    fn render(&mut self, wl_ctx: &mut WaylandCtx, id: usize, draw: impl FnOnce(&Context)) {
+        let output = &wl_ctx.foam_outputs[id];
+        let (global_x, global_y) = (output.global_x as f64, output.global_y as f64);
+        let (logical_width, logical_height) = output.logical_size();
+        let (width, height) = wl_ctx.overlay_buffer_size(id);
+        let (buffer, canvas) = wl_ctx
+            .create_buffer(width, height, width * 4, Format::Argb8888)
+            .unwrap();
+        canvas.fill(0);
+
+        let cairo_surface = unsafe {
+            ImageSurface::create_for_data(
+                std::slice::from_raw_parts_mut(canvas.as_mut_ptr(), canvas.len()),
+                cairo::Format::ARgb32,
+                width,
+                height,
+                width * 4,
+            )
+            .map_err(|e| format!("Failed to create Cairo surface: {}", e))
+            .unwrap()
+        };
+
+        // 创建 Cairo 上下文
+        let ctx = Context::new(&cairo_surface)
+            .map_err(|e| format!("Failed to create Cairo context: {}", e))
+            .unwrap();
+
+        // NOTE: 以下均在全局逻辑坐标中绘制，由 buffer 缩放比映射到物理像素
+        ctx.scale(
+            width as f64 / logical_width as f64,
+            height as f64 / logical_height as f64,
+        );
+        ctx.translate(-global_x, -global_y);
+        draw(&ctx);
+        drop(ctx);
+
+        cairo_surface.flush();
+
+        let surface = &self.surfaces[id];
+        buffer.attach_to(surface).unwrap();
+        self.buffers[id] = Some(buffer);
+        // 请求重绘
+        surface.damage_buffer(0, 0, cairo_surface.width(), cairo_surface.height());
+        surface.commit();
+    }
+
+    /// 销毁所有 layer 和 surface
+    pub fn destroy(&mut self) {
+        for layer in self.layer_surfaces.drain(..) {
+            layer.destroy();
+        }
+        for surface in self.surfaces.drain(..) {
+            surface.destroy();
+        }
+    }
+}
+
+/// 填充遮罩，清除 rect（全局逻辑坐标）以显示冻结画面
+fn draw_mask(ctx: &Context, colors: &Colors, rect: (f64, f64, f64, f64)) {
+    ctx.save().unwrap();
+    colors.overlay.set_source(ctx);
+    ctx.paint().unwrap();
+    ctx.set_operator(cairo::Operator::Clear);
+    ctx.rectangle(rect.0, rect.1, rect.2, rect.3);
+    ctx.fill().unwrap();
+    ctx.restore().unwrap();
+}
+
+/// 绘制遮罩、选区和尺寸标签，start 和 end 为全局坐标
+fn draw_selection(ctx: &Context, colors: &Colors, start: (f64, f64), end: (f64, f64)) {
+    let (start_x, start_y) = start;
+    let (end_x, end_y) = end;
+    // 填充遮罩并清除矩形区域以显示透明
+    draw_mask(
+        ctx,
+        colors,
+        (start_x, start_y, end_x - start_x, end_y - start_y),
+    );
+
+    // 确保宽度和高度为非负整数
+    let width = (end_x - start_x).abs() as i32;
+    let height = (end_y - start_y).abs() as i32;
+
+    // 创建文本内容
+    let text = format!("{}x{}", width, height);
+
+    // 计算文本位置，使其位于矩形的右下角外侧
+    let text_extent = ctx.text_extents(&text).unwrap();
+    let _text_width = text_extent.width();
+    let text_height = text_extent.height();
+
+    // 确定矩形的右下角坐标
+    let rect_end_x = f64::max(start_x, end_x);
+    let rect_end_y = f64::max(start_y, end_y);
+
+    // 文本位置在矩形的右下角外侧，沿对角线延伸
+    let text_x = rect_end_x + 10.0; // 向右偏移10像素
+    let text_y = rect_end_y + text_height + 10.0; // 向下偏移10像素加上文本高度
+
+    // 设置文本颜色
+    colors.label.set_source(ctx);
+    ctx.select_font_face("Sans", FontSlant::Normal, FontWeight::Normal);
+    ctx.set_font_size(16.0);
+
+    // 绘制文本
+    ctx.move_to(text_x, text_y);
+    ctx.show_text(&text).unwrap();
+}
+
+/// 选区四角和四边中点的控制柄
+fn draw_handles(ctx: &Context, colors: &Colors, adjust: &AdjustMode) {
+    const SIZE: f64 = 8.0;
+    ctx.save().unwrap();
+    ctx.set_line_width(1.0);
+    for (_, _, (hx, hy)) in adjust.handles() {
+        ctx.rectangle(hx - SIZE / 2.0, hy - SIZE / 2.0, SIZE, SIZE);
+        ctx.set_source_rgb(1.0, 1.0, 1.0);
+        ctx.fill_preserve().unwrap();
+        colors.label.set_source(ctx);
+        ctx.stroke().unwrap();
+    }
+    ctx.restore().unwrap();
+}
+
+/// 在选区左上角上方显示正在输入的 `x,y WxH`
+fn draw_input(ctx: &Context, colors: &Colors, input: &str, (x, y): (f64, f64)) {
+    let text = format!("{}_", input);
+    ctx.save().unwrap();
+    ctx.select_font_face("Monospace", FontSlant::Normal, FontWeight::Normal);
+    ctx.set_font_size(16.0);
+    let extents = ctx.text_extents(&text).unwrap();
+    let (padding, height) = (4.0, 20.0);
+    let top = (y - height - 6.0).max(0.0);
+    ctx.rectangle(x, top, extents.x_advance() + padding * 2.0, height);
+    ctx.set_source_rgb(1.0, 1.0, 1.0);
+    ctx.fill().unwrap();
+    colors.label.set_source(ctx);
+    ctx.move_to(x + padding, top + height - 5.0);
+    ctx.show_text(&text).unwrap();
+    ctx.restore().unwrap();
 }
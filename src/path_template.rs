@@ -0,0 +1,205 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local, format::StrftimeItems};
+use directories::BaseDirs;
+
+/// 截图信息，用于展开模板中 foamshot 特有的记号
+pub struct TemplateInfo<'a> {
+    /// 截图区域所在的输出名称，全屏截取所有输出时为 all
+    pub output: &'a str,
+    /// 选区的逻辑尺寸
+    pub width: i32,
+    pub height: i32,
+    pub full_screen: bool,
+}
+
+/// 输出路径模板。
+/// 支持 chrono 的全部 strftime 记号（如 %Y、%3f），以及：
+/// %n 自增序号，%o 输出名称，%w/%h 选区宽高，%K 截图方式（region/fullscreen），
+/// 开头的 `~` 和 `$HOME` 会展开为用户主目录。
+/// NOTE: %n、%w、%h 覆盖了 strftime 的换行、星期和月份缩写
+#[derive(Debug, Clone)]
+pub struct PathTemplate(String);
+
+impl PathTemplate {
+    /// 创建模板，strftime 记号无效时返回错误
+    pub fn new(template: String) -> Result<Self, String> {
+        let info = TemplateInfo {
+            output: "",
+            width: 0,
+            height: 0,
+            full_screen: false,
+        };
+        let expanded = expand_tokens(&template, &info, 1);
+        StrftimeItems::new(&expanded)
+            .parse()
+            .map_err(|e| format!("invalid output path template `{}`: {}", template, e))?;
+        Ok(Self(template))
+    }
+
+    /// `-` 表示写到 stdout
+    pub fn is_stdout(&self) -> bool {
+        self.0 == "-"
+    }
+
+    /// 以给定的时间、截图信息和序号展开模板
+    fn render(&self, now: &DateTime<Local>, info: &TemplateInfo, counter: u32) -> PathBuf {
+        let expanded = expand_tokens(&self.0, info, counter);
+        // NOTE: 模板已在 new 中校验过
+        let formatted = match StrftimeItems::new(&expanded).parse() {
+            Ok(items) => now.format_with_items(items.iter()).to_string(),
+            Err(_) => expanded,
+        };
+        expand_home(&formatted)
+    }
+
+    /// 展开模板并创建文件，%n 取第一个未被占用的序号。
+    /// 会创建缺失的目录，文件已存在时不覆盖，而是在扩展名前追加 -1、-2 …
+    pub fn create(&self, info: &TemplateInfo) -> io::Result<(PathBuf, File)> {
+        let now = Local::now();
+        let has_counter = self.render(&now, info, 1) != self.render(&now, info, 2);
+        let mut path = self.render(&now, info, 1);
+        let mut counter = 1;
+        while has_counter && path.exists() {
+            counter += 1;
+            path = self.render(&now, info, counter);
+        }
+
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path.extension().map(|e| e.to_string_lossy());
+        let mut candidate = path.clone();
+        let mut suffix = 0;
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&candidate)
+            {
+                Ok(file) => return Ok((candidate, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    suffix += 1;
+                    let name = match &extension {
+                        Some(ext) => format!("{}-{}.{}", stem, suffix, ext),
+                        None => format!("{}-{}", stem, suffix),
+                    };
+                    candidate = path.with_file_name(name);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// 替换 foamshot 特有的记号，其余记号（包括 %%）原样留给 strftime
+fn expand_tokens(template: &str, info: &TemplateInfo, counter: u32) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push_str(&counter.to_string()),
+            // NOTE: 名称中的 % 需要转义，避免被 strftime 解析
+            Some('o') => out.push_str(&info.output.replace('%', "%%")),
+            Some('w') => out.push_str(&info.width.to_string()),
+            Some('h') => out.push_str(&info.height.to_string()),
+            Some('K') => out.push_str(if info.full_screen {
+                "fullscreen"
+            } else {
+                "region"
+            }),
+            Some(c) => {
+                out.push('%');
+                out.push(c);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// 展开开头的 `~` 以及 `$HOME`、`${HOME}`
+fn expand_home(path: &str) -> PathBuf {
+    let Some(home) = BaseDirs::new().map(|d| d.home_dir().to_string_lossy().to_string()) else {
+        return PathBuf::from(path);
+    };
+    let path = path.replace("${HOME}", &home).replace("$HOME", &home);
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => PathBuf::from(home + rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn info(output: &str) -> TemplateInfo<'_> {
+        TemplateInfo {
+            output,
+            width: 640,
+            height: 480,
+            full_screen: false,
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let info = info("DP-1");
+        assert_eq!(expand_tokens("shot-%n", &info, 3), "shot-3");
+        assert_eq!(expand_tokens("%o/%wx%h", &info, 1), "DP-1/640x480");
+        assert_eq!(expand_tokens("%K", &info, 1), "region");
+        let full = TemplateInfo {
+            full_screen: true,
+            ..info
+        };
+        assert_eq!(expand_tokens("%K", &full, 1), "fullscreen");
+    }
+
+    #[test]
+    fn escaping() {
+        let info = info("100%");
+        // NOTE: strftime 记号和 %% 原样保留
+        assert_eq!(expand_tokens("%Y-%%n", &info, 1), "%Y-%%n");
+        assert_eq!(expand_tokens("%o", &info, 1), "100%%");
+        assert_eq!(expand_tokens("end%", &info, 1), "end%");
+    }
+
+    #[test]
+    fn render() {
+        let now = Local.with_ymd_and_hms(2024, 5, 6, 7, 8, 9).unwrap();
+        let template = PathTemplate::new("%Y-%m-%d_%o_%%n_%n".to_string()).unwrap();
+        assert_eq!(
+            template.render(&now, &info("a%b"), 2),
+            PathBuf::from("2024-05-06_a%b_%n_2")
+        );
+    }
+
+    #[test]
+    fn invalid_template() {
+        assert!(PathTemplate::new("%Q".to_string()).is_err());
+    }
+
+    #[test]
+    fn home() {
+        let Some(home) = BaseDirs::new().map(|d| d.home_dir().to_path_buf()) else {
+            return;
+        };
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/a.png"), home.join("a.png"));
+        assert_eq!(expand_home("$HOME/a.png"), home.join("a.png"));
+        assert_eq!(expand_home("${HOME}/a.png"), home.join("a.png"));
+        assert_eq!(expand_home("~user/a.png"), PathBuf::from("~user/a.png"));
+        assert_eq!(expand_home("/tmp/~"), PathBuf::from("/tmp/~"));
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use cairo::{Context, ImageSurface};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::annotation::Annotation;
+
+/// 工程文件的格式版本
+const VERSION: u32 = 1;
+
+/// 工程文件，记录未绘制标注的原始截图和标注列表，可用 `foamshot edit` 重新打开。
+/// 截图 shot.png 的工程文件为 shot.png.foamshot.toml，原始截图保存为 shot.png.orig.png
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    /// 原始截图，相对于工程文件所在的目录
+    pub image: PathBuf,
+    /// 截图区域的逻辑尺寸
+    pub width: f64,
+    pub height: f64,
+    /// 标注，坐标相对于截图区域的左上角
+    pub annotations: Vec<Annotation>,
+}
+
+impl Project {
+    /// 在 image_path 旁保存工程文件和原始截图，返回工程文件的路径。
+    /// base 为截图区域 region（全局逻辑坐标）的原始截图，fit 为 region 相对于工程尺寸的缩放比例，
+    /// 保存时标注和尺寸按 1 / fit 还原
+    pub fn save(
+        image_path: &Path,
+        base: &ImageSurface,
+        region: (f64, f64, f64, f64),
+        fit: f64,
+        annotations: &[Annotation],
+    ) -> Result<PathBuf, String> {
+        let (x, y, width, height) = region;
+        // NOTE: 使用完整的文件名，shot.png 和 shot.jpg 的工程互不覆盖；截图文件名已保证唯一
+        let name = image_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let image = PathBuf::from(format!("{}.orig.png", name));
+        let project_path = image_path.with_file_name(format!("{}.foamshot.toml", name));
+        // NOTE: 与截图相同，已存在的文件不覆盖
+        let create = |path: &Path| {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+                .map_err(|e| format!("failed to create {}: {}", path.display(), e))
+        };
+
+        let mut file = create(&image_path.with_file_name(&image))?;
+        base.write_to_png(&mut file)
+            .map_err(|e| format!("failed to write {}: {}", image.display(), e))?;
+
+        let project = Project {
+            version: VERSION,
+            image,
+            width: width / fit,
+            height: height / fit,
+            annotations: annotations
+                .iter()
+                .cloned()
+                .map(|mut annotation| {
+                    annotation.translate((-x, -y));
+                    annotation.zoom((0.0, 0.0), 1.0 / fit);
+                    annotation
+                })
+                .collect(),
+        };
+        let content =
+            toml::to_string(&project).map_err(|e| format!("failed to serialize project: {}", e))?;
+        create(&project_path)?
+            .write_all(content.as_bytes())
+            .map_err(|e| format!("failed to write {}: {}", project_path.display(), e))?;
+        info!("project path: {}", project_path.display());
+        Ok(project_path)
+    }
+
+    /// 读取工程文件和原始截图
+    pub fn load(path: &Path) -> Result<(Project, ImageSurface), String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read project {}: {}", path.display(), e))?;
+        let project: Project = toml::from_str(&content)
+            .map_err(|e| format!("invalid project {}:\n{}", path.display(), e))?;
+        if project.version > VERSION {
+            return Err(format!(
+                "project {} has unsupported version {}",
+                path.display(),
+                project.version
+            ));
+        }
+
+        let image_path = path.parent().unwrap_or(Path::new(".")).join(&project.image);
+        let mut file = File::open(&image_path)
+            .map_err(|e| format!("failed to open {}: {}", image_path.display(), e))?;
+        let png = ImageSurface::create_from_png(&mut file)
+            .map_err(|e| format!("failed to read {}: {}", image_path.display(), e))?;
+
+        // NOTE: 统一为与冻结画面相同的 Rgb24，打码按该格式读写像素
+        let image = ImageSurface::create(cairo::Format::Rgb24, png.width(), png.height())
+            .map_err(|e| format!("failed to create image: {}", e))?;
+        let ctx = Context::new(&image).map_err(|e| format!("failed to create image: {}", e))?;
+        ctx.set_source_surface(&png, 0.0, 0.0).unwrap();
+        ctx.paint().unwrap();
+        drop(ctx);
+        Ok((project, image))
+    }
+}
@@ -0,0 +1,176 @@
+use cairo::{Context, ImageSurface};
+use serde::{Deserialize, Serialize};
+
+/// 打码方式
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Redaction {
+    /// 马赛克
+    Pixelate,
+    /// 高斯模糊
+    Blur,
+    /// 纯黑填充
+    Fill,
+}
+
+impl Redaction {
+    /// 工具栏中的顺序
+    pub const ALL: [Redaction; 3] = [Redaction::Pixelate, Redaction::Blur, Redaction::Fill];
+}
+
+/// 截图区域的原始像素，打码从中取样
+pub struct Backdrop<'a> {
+    pub image: &'a ImageSurface,
+    /// 图片左上角的全局逻辑坐标
+    pub origin: (f64, f64),
+    /// 每个逻辑像素对应的图片像素数
+    pub scale: f64,
+}
+
+/// 马赛克块的边长和模糊的标准差，逻辑像素
+const BLOCK_SIZE: f64 = 10.0;
+const BLUR_SIGMA: f64 = 8.0;
+
+impl Backdrop<'_> {
+    /// 对全局逻辑坐标中的 rect 打码并绘制到 ctx。
+    /// 生成的图块只由原始像素计算，以 Source 覆盖，保存后原始像素不可恢复
+    pub fn redact(&self, ctx: &Context, rect: (f64, f64, f64, f64), redaction: Redaction) {
+        let (x, y, w, h) = rect;
+        let (ox, oy) = self.origin;
+        let left = (((x - ox) * self.scale).floor() as i32).max(0);
+        let top = (((y - oy) * self.scale).floor() as i32).max(0);
+        let right = (((x + w - ox) * self.scale).ceil() as i32).min(self.image.width());
+        let bottom = (((y + h - oy) * self.scale).ceil() as i32).min(self.image.height());
+        if right <= left || bottom <= top {
+            return;
+        }
+        let Some(mut patch) = self.crop((left, top, right - left, bottom - top)) else {
+            return;
+        };
+        {
+            let stride = patch.stride() as usize;
+            let (pw, ph) = (patch.width() as usize, patch.height() as usize);
+            let Ok(mut data) = patch.data() else {
+                return;
+            };
+            match redaction {
+                Redaction::Pixelate => {
+                    let block = (BLOCK_SIZE * self.scale).round().max(2.0) as usize;
+                    pixelate(&mut data, stride, pw, ph, block);
+                }
+                Redaction::Blur => blur(&mut data, stride, pw, ph, BLUR_SIGMA * self.scale),
+                // NOTE: Rgb24 的 X 字节被忽略，全 0 即黑色
+                Redaction::Fill => data.fill(0),
+            }
+        }
+        patch.mark_dirty();
+
+        // NOTE: 在图片像素坐标中绘制，保存时与截图像素一一对应
+        ctx.save().unwrap();
+        ctx.translate(ox, oy);
+        ctx.scale(1.0 / self.scale, 1.0 / self.scale);
+        ctx.set_operator(cairo::Operator::Source);
+        ctx.set_source_surface(&patch, left as f64, top as f64)
+            .unwrap();
+        ctx.rectangle(
+            left as f64,
+            top as f64,
+            (right - left) as f64,
+            (bottom - top) as f64,
+        );
+        ctx.fill().unwrap();
+        ctx.restore().unwrap();
+    }
+
+    /// 复制图片中 rect（图片像素坐标）内的像素
+    fn crop(&self, (x, y, w, h): (i32, i32, i32, i32)) -> Option<ImageSurface> {
+        let mut patch = ImageSurface::create(cairo::Format::Rgb24, w, h).ok()?;
+        let dst_stride = patch.stride() as usize;
+        let src_stride = self.image.stride() as usize;
+        self.image.flush();
+        {
+            let mut dst = patch.data().ok()?;
+            self.image
+                .with_data(|src| {
+                    for row in 0..h as usize {
+                        let src_start = (y as usize + row) * src_stride + x as usize * 4;
+                        let dst_start = row * dst_stride;
+                        dst[dst_start..dst_start + w as usize * 4]
+                            .copy_from_slice(&src[src_start..src_start + w as usize * 4]);
+                    }
+                })
+                .ok()?;
+        }
+        Some(patch)
+    }
+}
+
+/// 每个块填充为块内像素的平均值
+fn pixelate(data: &mut [u8], stride: usize, width: usize, height: usize, block: usize) {
+    for by in (0..height).step_by(block) {
+        for bx in (0..width).step_by(block) {
+            let (bw, bh) = (block.min(width - bx), block.min(height - by));
+            let mut sum = [0u32; 3];
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    let i = y * stride + x * 4;
+                    for c in 0..3 {
+                        sum[c] += data[i + c] as u32;
+                    }
+                }
+            }
+            let count = (bw * bh) as u32;
+            let average = sum.map(|s| (s / count) as u8);
+            for y in by..by + bh {
+                for x in bx..bx + bw {
+                    let i = y * stride + x * 4;
+                    data[i..i + 3].copy_from_slice(&average);
+                }
+            }
+        }
+    }
+}
+
+/// 用三次盒式模糊近似高斯模糊，边缘只取图块内的像素
+fn blur(data: &mut [u8], stride: usize, width: usize, height: usize, sigma: f64) {
+    // NOTE: 盒宽取自 sigma，三次盒式模糊的方差约等于 sigma^2
+    let radius = (((12.0 * sigma * sigma / 3.0 + 1.0).sqrt() - 1.0) / 2.0).round() as usize;
+    if radius == 0 {
+        return;
+    }
+    let mut buffer = vec![0u8; data.len()];
+    for _ in 0..3 {
+        box_blur(data, &mut buffer, stride, 4, width, height, radius);
+        box_blur(&buffer, data, 4, stride, height, width, radius);
+    }
+}
+
+/// 沿一个方向做盒式模糊。step 为沿模糊方向相邻像素的字节距离，
+/// line_step 为相邻行（或列）的字节距离，length 为每行的像素数，lines 为行数
+fn box_blur(
+    src: &[u8],
+    dst: &mut [u8],
+    line_step: usize,
+    step: usize,
+    length: usize,
+    lines: usize,
+    radius: usize,
+) {
+    let window = (2 * radius + 1) as u32;
+    let at = |line: usize, i: isize| -> usize {
+        let i = i.clamp(0, length as isize - 1) as usize;
+        line * line_step + i * step
+    };
+    for line in 0..lines {
+        for c in 0..3 {
+            let mut sum: u32 = (-(radius as isize)..=radius as isize)
+                .map(|i| src[at(line, i) + c] as u32)
+                .sum();
+            for i in 0..length {
+                dst[at(line, i as isize) + c] = (sum / window) as u8;
+                sum += src[at(line, i as isize + radius as isize + 1) + c] as u32;
+                sum -= src[at(line, i as isize - radius as isize) + c] as u32;
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use cairo::ImageSurface;
+
+/// 吸附距离，逻辑像素
+pub const SNAP_DISTANCE: f64 = 8.0;
+/// 相邻两列（行）亮度差的平均值超过该值时视为边缘，亮度为 0-255
+const EDGE_THRESHOLD: f64 = 40.0;
+
+/// 将 pos 的 x 和 y 分别吸附到 SNAP_DISTANCE 内最近的窗口边缘，没有时吸附到画面中最强的边缘。
+/// windows 为全局逻辑坐标的窗口；patch 为以 pos 为中心、边长 2 * SNAP_DISTANCE 的冻结画面
+pub fn snap(
+    pos: (f64, f64),
+    windows: &[(f64, f64, f64, f64)],
+    patch: Option<&ImageSurface>,
+) -> (f64, f64) {
+    let (mut xs, mut ys) = (Vec::new(), Vec::new());
+    // NOTE: 只有边所在的范围经过 pos 附近时才吸附
+    for &(x, y, w, h) in windows {
+        if pos.1 >= y - SNAP_DISTANCE && pos.1 <= y + h + SNAP_DISTANCE {
+            xs.extend([x, x + w]);
+        }
+        if pos.0 >= x - SNAP_DISTANCE && pos.0 <= x + w + SNAP_DISTANCE {
+            ys.extend([y, y + h]);
+        }
+    }
+    let edges = patch.and_then(image_edges);
+    let origin = (pos.0 - SNAP_DISTANCE, pos.1 - SNAP_DISTANCE);
+    let x = nearest(pos.0, &xs)
+        .or_else(|| edges.and_then(|(x, _)| x).map(|x| origin.0 + x))
+        .unwrap_or(pos.0);
+    let y = nearest(pos.1, &ys)
+        .or_else(|| edges.and_then(|(_, y)| y).map(|y| origin.1 + y))
+        .unwrap_or(pos.1);
+    (x, y)
+}
+
+/// SNAP_DISTANCE 内离 value 最近的候选
+fn nearest(value: f64, candidates: &[f64]) -> Option<f64> {
+    candidates
+        .iter()
+        .copied()
+        .filter(|c| (c - value).abs() <= SNAP_DISTANCE)
+        .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+}
+
+/// 画面中最强的竖直边缘和水平边缘，为相对于 patch 左上角的逻辑坐标。
+/// 以相邻两列（行）亮度差在整列（行）上的平均值作为边缘强度
+fn image_edges(patch: &ImageSurface) -> Option<(Option<f64>, Option<f64>)> {
+    let (width, height) = (patch.width() as usize, patch.height() as usize);
+    if width < 2 || height < 2 {
+        return None;
+    }
+    let scale = width as f64 / (2.0 * SNAP_DISTANCE);
+    let stride = patch.stride() as usize;
+    patch.flush();
+    let mut luma = vec![0.0; width * height];
+    patch
+        .with_data(|data| {
+            for y in 0..height {
+                for x in 0..width {
+                    // NOTE: cairo Rgb24 在小端机器上为 BGRX
+                    let i = y * stride + x * 4;
+                    luma[y * width + x] = 0.114 * data[i] as f64
+                        + 0.587 * data[i + 1] as f64
+                        + 0.299 * data[i + 2] as f64;
+                }
+            }
+        })
+        .ok()?;
+
+    let strongest = |scores: Vec<f64>| {
+        scores
+            .iter()
+            .enumerate()
+            .filter(|&(_, &score)| score >= EDGE_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            // NOTE: 边缘位于第 i 和 i + 1 个像素之间
+            .map(|(i, _)| (i + 1) as f64 / scale)
+    };
+    let columns = (0..width - 1)
+        .map(|x| {
+            (0..height)
+                .map(|y| (luma[y * width + x + 1] - luma[y * width + x]).abs())
+                .sum::<f64>()
+                / height as f64
+        })
+        .collect();
+    let rows = (0..height - 1)
+        .map(|y| {
+            (0..width)
+                .map(|x| (luma[(y + 1) * width + x] - luma[y * width + x]).abs())
+                .sum::<f64>()
+                / width as f64
+        })
+        .collect();
+    Some((strongest(columns), strongest(rows)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 边长 2 * SNAP_DISTANCE 的灰度图，columns 给出每列的亮度
+    fn patch(columns: impl Fn(usize) -> u8) -> ImageSurface {
+        let size = (2.0 * SNAP_DISTANCE) as i32;
+        let mut surface = ImageSurface::create(cairo::Format::Rgb24, size, size).unwrap();
+        let stride = surface.stride() as usize;
+        let mut data = surface.data().unwrap();
+        for y in 0..size as usize {
+            for x in 0..size as usize {
+                let v = columns(x);
+                data[y * stride + x * 4..][..4].copy_from_slice(&[v, v, v, 0xff]);
+            }
+        }
+        drop(data);
+        surface
+    }
+
+    #[test]
+    fn snaps_to_window_edge() {
+        let windows = [(100.0, 100.0, 200.0, 150.0)];
+        assert_eq!(snap((105.0, 96.0), &windows, None), (100.0, 100.0));
+        assert_eq!(snap((297.0, 252.0), &windows, None), (300.0, 250.0));
+        // NOTE: 多个候选时取最近的
+        let windows = [(100.0, 0.0, 10.0, 300.0)];
+        assert_eq!(snap((106.0, 50.0), &windows, None), (110.0, 50.0));
+    }
+
+    #[test]
+    fn no_snap_outside_distance() {
+        let windows = [(100.0, 100.0, 200.0, 150.0)];
+        assert_eq!(snap((120.0, 120.0), &windows, None), (120.0, 120.0));
+        // NOTE: 边所在的范围没有经过 pos 附近
+        assert_eq!(snap((102.0, 400.0), &windows, None), (102.0, 400.0));
+    }
+
+    #[test]
+    fn window_edge_before_image_edge() {
+        let windows = [(98.0, 0.0, 50.0, 300.0)];
+        let patch = patch(|x| if x < 10 { 0 } else { 255 });
+        assert_eq!(snap((100.0, 100.0), &windows, Some(&patch)).0, 98.0);
+    }
+
+    #[test]
+    fn strongest_image_edge() {
+        // NOTE: 第 4、5 列之间亮度差 60，第 9、10 列之间亮度差 255，取更强的
+        let patch = patch(|x| match x {
+            0..5 => 60,
+            5..10 => 0,
+            _ => 255,
+        });
+        assert_eq!(image_edges(&patch), Some((Some(10.0), None)));
+        assert_eq!(snap((100.0, 100.0), &[], Some(&patch)), (102.0, 100.0));
+    }
+
+    #[test]
+    fn weak_image_edge() {
+        let patch = patch(|x| if x < 8 { 100 } else { 120 });
+        assert_eq!(image_edges(&patch), Some((None, None)));
+        assert_eq!(snap((100.0, 100.0), &[], Some(&patch)), (100.0, 100.0));
+    }
+}
@@ -0,0 +1,276 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{debug, info};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+/// 等待合成器回复的最长时间
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 窗口区域 (x, y, width, height)，全局逻辑坐标
+pub type Rect = (f64, f64, f64, f64);
+
+/// 通过合成器的 IPC 获取可见窗口的区域（全局逻辑坐标），越靠前的窗口越在上层。
+/// 支持 sway、Hyprland 和 niri；wlr 和 ext 的 foreign-toplevel 协议不提供窗口位置，因此不使用。
+/// outputs 为各输出的名称和左上角的全局逻辑坐标。不支持当前合成器或查询失败时返回空列表
+pub fn query(outputs: &[(String, (f64, f64))]) -> Vec<Rect> {
+    let result = if let Ok(path) = std::env::var("SWAYSOCK") {
+        sway(PathBuf::from(path))
+    } else if let Ok(signature) = std::env::var("HYPRLAND_INSTANCE_SIGNATURE") {
+        hyprland(&signature)
+    } else if let Ok(path) = std::env::var("NIRI_SOCKET") {
+        niri(PathBuf::from(path), outputs)
+    } else {
+        debug!("no compositor ipc available, window selection disabled");
+        return Vec::new();
+    };
+    match result {
+        Ok(windows) => {
+            info!("{} windows from compositor ipc", windows.len());
+            windows
+        }
+        Err(e) => {
+            info!("failed to query windows: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn connect(path: &PathBuf) -> Result<UnixStream, String> {
+    let stream = UnixStream::connect(path)
+        .map_err(|e| format!("failed to connect to {}: {}", path.display(), e))?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(|e| format!("failed to set timeout: {}", e))?;
+    Ok(stream)
+}
+
+fn parse<T: DeserializeOwned>(data: &[u8]) -> Result<T, String> {
+    serde_json::from_slice(data).map_err(|e| format!("invalid ipc reply: {}", e))
+}
+
+fn from_value<T: DeserializeOwned>(value: serde_json::Value) -> Result<T, String> {
+    serde_json::from_value(value).map_err(|e| format!("invalid ipc reply: {}", e))
+}
+
+#[derive(Deserialize)]
+struct SwayRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    /// root、output、workspace、con 或 floating_con
+    #[serde(rename = "type")]
+    kind: String,
+    rect: SwayRect,
+    /// 窗口内容相对于 rect 的区域，不含边框和标题栏
+    window_rect: Option<SwayRect>,
+    visible: Option<bool>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+/// i3 IPC 的 GET_TREE
+fn sway(path: PathBuf) -> Result<Vec<(f64, f64, f64, f64)>, String> {
+    const MAGIC: &[u8] = b"i3-ipc";
+    const GET_TREE: u32 = 4;
+    let mut stream = connect(&path)?;
+    let mut request = MAGIC.to_vec();
+    request.extend(0u32.to_ne_bytes());
+    request.extend(GET_TREE.to_ne_bytes());
+    stream
+        .write_all(&request)
+        .map_err(|e| format!("failed to write ipc request: {}", e))?;
+
+    let mut header = [0u8; 14];
+    stream
+        .read_exact(&mut header)
+        .map_err(|e| format!("failed to read ipc reply: {}", e))?;
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+    let mut payload = vec![0u8; length];
+    stream
+        .read_exact(&mut payload)
+        .map_err(|e| format!("failed to read ipc reply: {}", e))?;
+    let tree: SwayNode = parse(&payload)?;
+
+    let mut windows = Vec::new();
+    collect_sway(&tree, &mut windows);
+    Ok(windows)
+}
+
+/// 浮动窗口在平铺窗口之上，先收集
+fn collect_sway(node: &SwayNode, windows: &mut Vec<(f64, f64, f64, f64)>) {
+    for child in node.floating_nodes.iter().chain(node.nodes.iter()) {
+        collect_sway(child, windows);
+    }
+    let is_window = matches!(node.kind.as_str(), "con" | "floating_con") && node.nodes.is_empty();
+    if is_window && node.visible == Some(true) {
+        let SwayRect {
+            x,
+            y,
+            width,
+            height,
+        } = node.rect;
+        windows.push(match &node.window_rect {
+            Some(inner) => (x + inner.x, y + inner.y, inner.width, inner.height),
+            None => (x, y, width, height),
+        });
+    }
+}
+
+#[derive(Deserialize)]
+struct HyprWorkspace {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HyprMonitor {
+    active_workspace: HyprWorkspace,
+    special_workspace: HyprWorkspace,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HyprClient {
+    at: (f64, f64),
+    size: (f64, f64),
+    mapped: bool,
+    hidden: bool,
+    floating: bool,
+    workspace: HyprWorkspace,
+    /// 0 为当前聚焦的窗口，越大越久未聚焦
+    #[serde(rename = "focusHistoryID")]
+    focus_history_id: i64,
+}
+
+/// Hyprland 的 j/monitors 和 j/clients
+fn hyprland(signature: &str) -> Result<Vec<(f64, f64, f64, f64)>, String> {
+    // NOTE: 新版本的 socket 在 XDG_RUNTIME_DIR 下，旧版本在 /tmp 下
+    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_default();
+    let path = [
+        PathBuf::from(runtime).join("hypr"),
+        PathBuf::from("/tmp/hypr"),
+    ]
+    .into_iter()
+    .map(|dir| dir.join(signature).join(".socket.sock"))
+    .find(|path| path.exists())
+    .ok_or("hyprland socket not found")?;
+    let request = |command: &str| -> Result<Vec<u8>, String> {
+        let mut stream = connect(&path)?;
+        stream
+            .write_all(command.as_bytes())
+            .map_err(|e| format!("failed to write ipc request: {}", e))?;
+        let mut reply = Vec::new();
+        stream
+            .read_to_end(&mut reply)
+            .map_err(|e| format!("failed to read ipc reply: {}", e))?;
+        Ok(reply)
+    };
+
+    let monitors: Vec<HyprMonitor> = parse(&request("j/monitors")?)?;
+    let special: Vec<i64> = monitors
+        .iter()
+        .map(|m| m.special_workspace.id)
+        .filter(|&id| id != 0)
+        .collect();
+    let mut clients: Vec<HyprClient> = parse(&request("j/clients")?)?;
+    clients.retain(|c| {
+        c.mapped
+            && !c.hidden
+            && (special.contains(&c.workspace.id)
+                || monitors
+                    .iter()
+                    .any(|m| m.active_workspace.id == c.workspace.id))
+    });
+    // NOTE: IPC 不提供层叠顺序，特殊工作区在最上，浮动窗口在平铺窗口之上，其次按最近聚焦排序
+    clients.sort_by_key(|c| {
+        (
+            !special.contains(&c.workspace.id),
+            !c.floating,
+            c.focus_history_id,
+        )
+    });
+    Ok(clients
+        .iter()
+        .map(|c| (c.at.0, c.at.1, c.size.0, c.size.1))
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct NiriWorkspace {
+    id: u64,
+    output: Option<String>,
+    is_active: bool,
+}
+
+#[derive(Deserialize)]
+struct NiriLayout {
+    window_size: (f64, f64),
+    /// 不在视图内的窗口为 None
+    tile_pos_in_workspace_view: Option<(f64, f64)>,
+    window_offset_in_tile: (f64, f64),
+}
+
+#[derive(Deserialize)]
+struct NiriWindow {
+    workspace_id: Option<u64>,
+    is_floating: bool,
+    /// 旧版本的 niri 不提供
+    layout: Option<NiriLayout>,
+}
+
+/// niri 的 Workspaces 和 Windows，窗口位置相对于所在输出
+fn niri(
+    path: PathBuf,
+    outputs: &[(String, (f64, f64))],
+) -> Result<Vec<(f64, f64, f64, f64)>, String> {
+    let request = |name: &str| -> Result<serde_json::Value, String> {
+        let mut stream = connect(&path)?;
+        stream
+            .write_all(format!("\"{}\"\n", name).as_bytes())
+            .map_err(|e| format!("failed to write ipc request: {}", e))?;
+        let mut reply = String::new();
+        std::io::BufRead::read_line(&mut std::io::BufReader::new(stream), &mut reply)
+            .map_err(|e| format!("failed to read ipc reply: {}", e))?;
+        let mut reply: serde_json::Value = parse(reply.as_bytes())?;
+        reply
+            .get_mut("Ok")
+            .and_then(|ok| ok.get_mut(name))
+            .map(serde_json::Value::take)
+            .ok_or_else(|| format!("niri replied with an error to {}", name))
+    };
+    let workspaces: Vec<NiriWorkspace> = from_value(request("Workspaces")?)?;
+    let mut windows: Vec<NiriWindow> = from_value(request("Windows")?)?;
+    // NOTE: 浮动窗口在平铺窗口之上
+    windows.sort_by_key(|w| !w.is_floating);
+    Ok(windows
+        .iter()
+        .filter_map(|window| {
+            let workspace = workspaces
+                .iter()
+                .find(|ws| Some(ws.id) == window.workspace_id && ws.is_active)?;
+            let name = workspace.output.as_deref()?;
+            let (_, (output_x, output_y)) = outputs.iter().find(|(n, _)| n == name)?;
+            let layout = window.layout.as_ref()?;
+            let (tile_x, tile_y) = layout.tile_pos_in_workspace_view?;
+            let (offset_x, offset_y) = layout.window_offset_in_tile;
+            let (width, height) = layout.window_size;
+            Some((
+                output_x + tile_x + offset_x,
+                output_y + tile_y + offset_y,
+                width,
+                height,
+            ))
+        })
+        .collect())
+}
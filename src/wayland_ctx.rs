@@ -1,45 +1,76 @@
+use std::time::Instant;
+
 use smithay_client_toolkit::shm::{
     self,
     slot::{self, Buffer},
 };
 use wayland_client::{
     QueueHandle,
-    protocol::{wl_compositor, wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm::Format},
+    protocol::{
+        wl_compositor, wl_data_device_manager, wl_keyboard, wl_pointer, wl_seat, wl_shm::Format,
+        wl_surface,
+    },
 };
 use wayland_protocols::{
-    wp::cursor_shape::v1::client::{wp_cursor_shape_device_v1, wp_cursor_shape_manager_v1},
-    xdg::shell::client::xdg_wm_base,
+    wp::{
+        cursor_shape::v1::client::{wp_cursor_shape_device_v1, wp_cursor_shape_manager_v1},
+        fractional_scale::v1::client::wp_fractional_scale_manager_v1,
+        viewporter::client::{wp_viewport, wp_viewporter},
+    },
+    xdg::{shell::client::xdg_wm_base, xdg_output::zv1::client::zxdg_output_manager_v1},
 };
 use wayland_protocols_wlr::{
+    data_control::v1::client::zwlr_data_control_manager_v1,
     layer_shell::v1::client::zwlr_layer_shell_v1,
     screencopy::v1::client::zwlr_screencopy_manager_v1,
 };
 
+use xkbcommon::xkb;
+
+use crate::foam_output::FoamOutput;
 use crate::foam_shot::FoamShot;
 
 #[derive(Default)]
 pub struct WaylandCtx {
     pub compositor: Option<wl_compositor::WlCompositor>,
-    pub output: Option<wl_output::WlOutput>,
+    pub foam_outputs: Vec<FoamOutput>,
     pub pool: Option<slot::SlotPool>,
     pub shm: Option<shm::Shm>,
     pub seat: Option<wl_seat::WlSeat>,
     pub pointer: Option<wl_pointer::WlPointer>,
     pub keyboard: Option<wl_keyboard::WlKeyboard>,
+    /// 最近一次输入事件的 serial，设置剪贴板时需要
+    pub serial: Option<u32>,
+    /// 由 wl_keyboard keymap 创建的 xkb 状态
+    pub xkb_state: Option<xkb::State>,
+    /// 由当前 locale 的 compose 表创建的状态，用于死键和 Compose 键输入
+    pub compose_state: Option<xkb::compose::State>,
+    /// wl_keyboard repeat_info 的每秒重复次数和首次重复前的延迟（毫秒），rate 为 0 时不重复
+    pub repeat_info: (i32, i32),
+    /// 按住的可重复按键及下一次重复的时间
+    pub key_repeat: Option<(u32, Instant)>,
     pub layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
     pub xdg_shell: Option<xdg_wm_base::XdgWmBase>,
+    pub xdg_output_manager: Option<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
     pub qh: Option<QueueHandle<FoamShot>>,
 
     pub cursor_shape_manager: Option<wp_cursor_shape_manager_v1::WpCursorShapeManagerV1>,
     pub cursor_shape_device: Option<wp_cursor_shape_device_v1::WpCursorShapeDeviceV1>,
 
+    pub data_device_manager: Option<wl_data_device_manager::WlDataDeviceManager>,
+    pub data_control_manager: Option<zwlr_data_control_manager_v1::ZwlrDataControlManagerV1>,
+
+    pub viewporter: Option<wp_viewporter::WpViewporter>,
+    pub fractional_scale_manager:
+        Option<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1>,
+
+    /// 鼠标当前所在的输出
+    pub pointer_output: Option<usize>,
+    // NOTE: 以下坐标均为全局逻辑坐标
     pub current_pos: Option<(f64, f64)>,
     pub start_pos: Option<(f64, f64)>,
     pub end_pos: Option<(f64, f64)>,
     pub screencopy_manager: Option<zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1>,
-
-    pub width: Option<i32>,
-    pub height: Option<i32>,
 }
 
 impl WaylandCtx {
@@ -52,7 +83,87 @@ impl WaylandCtx {
         }
     }
 
-    /// Create a buffer
+    /// 按名称或描述查找输出，返回输出 id
+    pub fn find_output(&self, name: &str) -> Option<usize> {
+        self.foam_outputs
+            .iter()
+            .find(|o| o.matches(name))
+            .map(|o| o.id)
+    }
+
+    /// 所有输出在全局逻辑坐标中的外接矩形
+    pub fn layout_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let mut rects = self.foam_outputs.iter().map(|o| o.global_rect());
+        let (x, y, w, h) = rects.next()?;
+        let (left, top, right, bottom) = rects.fold((x, y, x + w, y + h), |acc, (x, y, w, h)| {
+            (
+                acc.0.min(x),
+                acc.1.min(y),
+                acc.2.max(x + w),
+                acc.3.max(y + h),
+            )
+        });
+        Some((left, top, right - left, bottom - top))
+    }
+
+    /// 为每个输出请求 xdg-output，以获取逻辑布局
+    pub fn request_xdg_outputs(&mut self) {
+        let (Some(manager), Some(qh)) = (self.xdg_output_manager.as_ref(), self.qh.as_ref()) else {
+            return;
+        };
+        for output in self.foam_outputs.iter_mut() {
+            if output.xdg_output.is_none() {
+                output.xdg_output = Some(manager.get_xdg_output(&output.wl_output, qh, output.id));
+            }
+        }
+    }
+
+    /// 输出上覆盖层 buffer 的渲染缩放比。
+    /// 分数缩放需要 wp_viewporter，否则使用输出的整数缩放比
+    pub fn overlay_scale(&self, id: usize) -> f64 {
+        let output = &self.foam_outputs[id];
+        if self.viewporter.is_some() {
+            output
+                .preferred_scale
+                .unwrap_or_else(|| output.buffer_scale())
+        } else {
+            output.scale as f64
+        }
+    }
+
+    /// 输出上覆盖层 buffer 的尺寸
+    pub fn overlay_buffer_size(&self, id: usize) -> (i32, i32) {
+        let (w, h) = self.foam_outputs[id].logical_size();
+        let scale = self.overlay_scale(id);
+        (
+            (w as f64 * scale).round() as i32,
+            (h as f64 * scale).round() as i32,
+        )
+    }
+
+    /// 让输出上的 surface 以输出的逻辑尺寸显示 buffer。
+    /// 有 wp_viewporter 时使用 viewport，否则使用整数的 buffer scale
+    pub fn scale_surface(
+        &self,
+        surface: &wl_surface::WlSurface,
+        id: usize,
+    ) -> Option<wp_viewport::WpViewport> {
+        let output = &self.foam_outputs[id];
+        match (self.viewporter.as_ref(), self.qh.as_ref()) {
+            (Some(viewporter), Some(qh)) => {
+                let (w, h) = output.logical_size();
+                let viewport = viewporter.get_viewport(surface, qh, ());
+                viewport.set_destination(w, h);
+                Some(viewport)
+            }
+            _ => {
+                surface.set_buffer_scale(output.scale);
+                None
+            }
+        }
+    }
+
+    /// 从共享内存池创建 buffer
     pub fn create_buffer(
         &mut self,
         width: i32,
@@ -69,7 +180,14 @@ impl WaylandCtx {
         Ok((buffer, canvas))
     }
 
-    /// Set the cursor shape
+    /// 键盘修饰键 name（如 xkb::MOD_NAME_ALT）是否按下
+    pub fn modifier_active(&self, name: &str) -> bool {
+        self.xkb_state
+            .as_ref()
+            .is_some_and(|state| state.mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE))
+    }
+
+    /// 设置鼠标形状
     pub fn set_cursor_shape(&mut self, shape: wp_cursor_shape_device_v1::Shape) {
         if let Some(device) = &self.cursor_shape_device {
             device.set_shape(1, shape);